@@ -1,10 +1,13 @@
 use anyhow::{Context, Result};
 use fs_extra::dir::{copy, CopyOptions};
 use std::fs;
-use std::path::{Path, PathBuf};
+use std::path::Path;
 use std::time::{SystemTime, UNIX_EPOCH};
 
-use crate::config::Config;
+use crate::config::{Config, ValidationMode};
+use crate::sync::{crypto, index, process, remap, validate, version};
+use crate::sync::restore::{backup_payload, RestoreSession};
+use crate::sync::snapshot::{self, Snapshot, GAME_CONFIG_SERIES};
 
 pub fn backup_game_config(config: &Config) -> Result<()> {
     config.ensure_backup_dir()?;
@@ -14,7 +17,8 @@ pub fn backup_game_config(config: &Config) -> Result<()> {
         .unwrap()
         .as_secs();
     
-    let backup_path = config.backup_dir.join(format!("game_config_{}", timestamp));
+    let staged = crypto::StagedSnapshot::new(config, &config.backup_dir.join(format!("{}_{}", GAME_CONFIG_SERIES, timestamp)))?;
+    let backup_path = staged.path();
     
     if !Path::new(&config.game_config_path).exists() {
         return Err(anyhow::anyhow!(
@@ -23,9 +27,32 @@ pub fn backup_game_config(config: &Config) -> Result<()> {
         ));
     }
     
+    // 客户端写入中途崩溃会留下空的或截断的 PersistedSettings.json，不能让它替代正常的快照
+    let files = index::scan_tree(&config.game_config_path)?;
+    let previous = snapshot::list_snapshots(config, GAME_CONFIG_SERIES)?
+        .first()
+        .and_then(|last| snapshot::load_meta(config, last).ok())
+        .and_then(|meta| meta.manifest);
+    let problems = validate::check_files(&config.game_config_path, "游戏配置", &files, previous.as_deref());
+    if !problems.is_empty() {
+        println!("警告: 游戏配置中的设置文件未通过校验:");
+        for problem in &problems {
+            println!("  {}", problem);
+        }
+        
+        if config.backup_validation == ValidationMode::Skip {
+            println!("已跳过游戏配置，没有创建快照。");
+            return Ok(());
+        }
+    }
+    
     if Path::new(&config.game_config_path).is_dir() {
-        // 复制目录
-        let options = CopyOptions::new();
+        // 复制目录的内容到快照根目录。快照目录此时还不存在，默认选项会在其中非递归地创建同名子目录而失败；
+        // 内容直接放在根目录也让清单路径与校验时扫描 game_config_path 得到的路径一致
+        let options = CopyOptions {
+            copy_inside: true,
+            ..CopyOptions::new()
+        };
         copy(&config.game_config_path, &backup_path, &options)
             .with_context(|| {
                 format!(
@@ -43,6 +70,15 @@ pub fn backup_game_config(config: &Config) -> Result<()> {
         })?;
     }
     
+    let manifest = index::scan_tree(&backup_path)?;
+    let backup_path = staged.finish()?;
+    snapshot::save_manifest(config, &backup_path, manifest)?;
+    version::record_version(config, &backup_path)?;
+    if !problems.is_empty() {
+        snapshot::mark_suspect(config, &backup_path, problems)?;
+        println!("快照已标记为可疑，恢复时需要强制确认。");
+    }
+    
     println!("游戏配置已成功备份到 {:?}", backup_path);
     
     Ok(())
@@ -59,7 +95,7 @@ pub fn restore_game_config(config: &Config) -> Result<()> {
     // 显示带时间戳的备份
     println!("可用的游戏配置备份:");
     for (i, backup) in backups.iter().enumerate() {
-        println!("  {}. {:?}", i + 1, backup);
+        println!("  {}. {:?}{}", i + 1, backup.path, snapshot::describe(config, &backup.path));
     }
     
     // 让用户选择一个备份
@@ -76,77 +112,86 @@ pub fn restore_game_config(config: &Config) -> Result<()> {
         }
     };
     
-    let selected_backup = &backups[selection];
+    let selected_backup = &backups[selection].path;
     
-    // 确保目标目录存在
-    if let Some(parent) = config.game_config_path.parent() {
-        fs::create_dir_all(parent)
-            .with_context(|| format!("创建父目录失败: {:?}", parent))?;
+    if !validate::confirm_restore(config, selected_backup)? || !version::confirm_version(config, selected_backup)? {
+        println!("恢复已取消。");
+        return Ok(());
     }
     
-    // 如果存在，则删除现有配置
-    if Path::new(&config.game_config_path).exists() {
-        if Path::new(&config.game_config_path).is_dir() {
-            fs::remove_dir_all(&config.game_config_path).with_context(|| {
-                format!("删除现有游戏配置目录失败: {:?}", config.game_config_path)
-            })?;
-        } else {
-            fs::remove_file(&config.game_config_path).with_context(|| {
-                format!("删除现有游戏配置文件失败: {:?}", config.game_config_path)
-            })?;
-        }
+    // 客户端在运行时恢复没有意义，它退出时会覆盖恢复的文件
+    if !process::ensure_game_closed(config)? {
+        println!("恢复已取消。");
+        return Ok(());
     }
     
-    // 从备份恢复
-    if Path::new(selected_backup).is_dir() {
-        let options = CopyOptions::new();
-        copy(selected_backup, config.game_config_path.parent().unwrap(), &options)
-            .with_context(|| {
-                format!(
-                    "从 {:?} 恢复游戏配置到 {:?} 失败",
-                    selected_backup, config.game_config_path
-                )
-            })?;
-    } else {
-        fs::copy(selected_backup, &config.game_config_path).with_context(|| {
-            format!(
-                "从 {:?} 恢复游戏配置到 {:?} 失败",
-                selected_backup, config.game_config_path
-            )
-        })?;
+    if !remap::confirm_targets(config, &[("游戏配置", config.game_config_path.as_path())])? {
+        println!("恢复已取消。");
+        return Ok(());
     }
     
+    // 拍摄安全快照后暂存恢复，完整后再替换现有配置
+    let content = snapshot::open_snapshot(config, selected_backup)?;
+    let payload = backup_payload(content.path(), &config.game_config_path);
+    let target = remap::remap_path(config, &config.game_config_path);
+    let mut session = RestoreSession::begin(config);
+    session.restore(&payload, &target).with_context(|| {
+        format!(
+            "从 {:?} 恢复游戏配置到 {:?} 失败",
+            selected_backup, target
+        )
+    })?;
+    
     println!("游戏配置已成功从 {:?} 恢复", selected_backup);
     
     Ok(())
 }
 
-fn list_game_config_backups(config: &Config) -> Result<Vec<PathBuf>> {
-    if !config.backup_dir.exists() {
-        return Ok(Vec::new());
+fn list_game_config_backups(config: &Config) -> Result<Vec<Snapshot>> {
+    // 最新的在前，带有标签时可以按标签筛选
+    snapshot::filter_by_tag(config, snapshot::list_snapshots(config, GAME_CONFIG_SERIES)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sync::snapshot::ScratchDir;
+    
+    fn write(path: &Path, text: &str) {
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(path, text).unwrap();
     }
     
-    let entries = fs::read_dir(&config.backup_dir)
-        .with_context(|| format!("读取备份目录失败: {:?}", config.backup_dir))?;
-    
-    let mut backups = Vec::new();
-    
-    for entry in entries {
-        let entry = entry?;
-        let path = entry.path();
-        let file_name = path.file_name().unwrap().to_string_lossy();
-        
-        if file_name.starts_with("game_config_") {
-            backups.push(path);
+    fn test_config(scratch: &ScratchDir) -> Config {
+        Config {
+            backup_dir: scratch.path().join("backups"),
+            game_config_path: scratch.path().join("game").join("Config"),
+            ..Config::default()
         }
     }
     
-    // 按修改时间排序（最新的在前）
-    backups.sort_by(|a, b| {
-        let a_meta = fs::metadata(a).unwrap();
-        let b_meta = fs::metadata(b).unwrap();
-        b_meta.modified().unwrap().cmp(&a_meta.modified().unwrap())
-    });
+    #[test]
+    fn directory_snapshot_restores_from_content_layout() {
+        let scratch = ScratchDir::new("game_config_layout");
+        let config = test_config(&scratch);
+        write(&config.game_config_path.join("Profiles").join("default.ini"), "[HUD]\nScale=50\n");
+        
+        backup_game_config(&config).unwrap();
+        
+        let snap = snapshot::list_snapshots(&config, GAME_CONFIG_SERIES).unwrap().remove(0);
+        let payload = backup_payload(&snap.path, &config.game_config_path);
+        assert_eq!(payload, snap.path);
+        assert!(payload.join("Profiles").join("default.ini").is_file());
+    }
     
-    Ok(backups)
-} 
\ No newline at end of file
+    #[test]
+    fn directory_snapshot_restores_from_named_layout() {
+        let scratch = ScratchDir::new("game_config_named");
+        let config = test_config(&scratch);
+        let snap_path = config.backup_dir.join(format!("{}_1", GAME_CONFIG_SERIES));
+        write(&snap_path.join("Config").join("game.cfg"), "[General]\nWindowMode=1\n");
+        
+        let payload = backup_payload(&snap_path, &config.game_config_path);
+        assert!(payload.join("game.cfg").is_file());
+    }
+}
\ No newline at end of file
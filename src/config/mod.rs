@@ -13,11 +13,134 @@ pub struct Account {
     pub note: Option<String>,
 }
 
+// 备份保留策略，未设置的规则不参与判断；全部未设置时保留所有备份
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct RetentionPolicy {
+    pub keep_last: Option<usize>,
+    pub keep_daily: Option<usize>,
+    pub keep_weekly: Option<usize>,
+    pub keep_monthly: Option<usize>,
+    pub max_total_size_mb: Option<u64>,
+}
+
+// 同步项目快照的存储方式：普通目录或单个压缩包
+#[derive(Debug, Clone, Copy, PartialEq, Default, Serialize, Deserialize)]
+pub enum StorageMode {
+    #[default]
+    Directory,
+    Archive,
+}
+
+// 备份前校验发现问题时的处理方式
+#[derive(Debug, Clone, Copy, PartialEq, Default, Serialize, Deserialize)]
+pub enum ValidationMode {
+    // 仍然创建快照，但标记为可疑
+    #[default]
+    Warn,
+    // 不创建快照
+    Skip,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Default, Serialize, Deserialize)]
+pub enum ArchiveFormat {
+    #[default]
+    TarZst,
+    Zip,
+}
+
+// 备份目录的加密设置，密码和密钥文件二选一
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct EncryptionConfig {
+    pub passphrase: Option<String>,
+    pub key_file: Option<PathBuf>,
+}
+
+// 游戏退出时自动备份的设置
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ProcessMonitorConfig {
+    // 游戏和客户端的进程名，其中任一进程在运行都视为游戏在运行
+    pub process_names: Vec<String>,
+    // 游戏退出后备份的组；为空时备份所有启用的项目
+    pub backup_group: Option<String>,
+}
+
+impl Default for ProcessMonitorConfig {
+    fn default() -> Self {
+        ProcessMonitorConfig {
+            process_names: vec![
+                "League of Legends.exe".to_string(),
+                "LeagueClient.exe".to_string(),
+                "LeagueClientUx.exe".to_string(),
+            ],
+            backup_group: None,
+        }
+    }
+}
+
+// 同步组的自动备份计划：固定间隔或 cron 表达式（分 时 日 月 周）
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum BackupSchedule {
+    Interval { minutes: u64 },
+    Cron { expression: String },
+}
+
+// 同步项目的黄金快照：源路径应当始终与之一致
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GoldenSnapshot {
+    // 备份目录中的快照名称
+    pub snapshot: String,
+    // 发现漂移时自动恢复黄金快照；为 false 时只报告
+    #[serde(default)]
+    pub auto_heal: bool,
+}
+
+// 恢复时的路径前缀替换，用于安装位置不同的电脑
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PathRemap {
+    pub from: PathBuf,
+    pub to: PathBuf,
+}
+
+// INI 中的一个键
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct IniKey {
+    pub section: String,
+    pub key: String,
+}
+
+// 部分恢复时从快照合并到当前文件的内容，保存后可以重复使用；INI 文件使用节和键，JSON 文件使用路径
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RestoreSelection {
+    pub name: String,
+    // 选择所属的同步项目，只在恢复该项目时提供；旧版本保存的选择为空
+    #[serde(default)]
+    pub item: String,
+    // 文件在项目中的相对路径（如 "input.ini"），单文件项目为空
+    pub file: String,
+    // 整节恢复的节名
+    #[serde(default)]
+    pub sections: Vec<String>,
+    // 单独恢复的键
+    #[serde(default)]
+    pub keys: Vec<IniKey>,
+    // JSON 文件中要恢复的路径，如 "files[Game.cfg].sections[HUD]"
+    #[serde(default)]
+    pub paths: Vec<String>,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct SyncGroup {
     pub name: String,
     pub description: Option<String>,
     pub is_enabled: bool,
+    #[serde(default)]
+    pub retention: Option<RetentionPolicy>,
+    // 为空时使用全局的存储方式
+    #[serde(default)]
+    pub storage_mode: Option<StorageMode>,
+    // 为空时只能手动备份
+    #[serde(default)]
+    pub schedule: Option<BackupSchedule>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -27,16 +150,73 @@ pub struct SyncItem {
     pub backup_path: PathBuf,
     pub is_enabled: bool,
     pub group: Option<String>,
+    #[serde(default)]
+    pub retention: Option<RetentionPolicy>,
+    // gitignore 风格的过滤规则，仅对目录类型的项目生效；include 为空表示包含全部
+    #[serde(default)]
+    pub include: Vec<String>,
+    #[serde(default = "default_exclude_patterns")]
+    pub exclude: Vec<String>,
+    // 恢复后把文件设为只读，防止客户端启动时覆盖恢复的设置
+    #[serde(default)]
+    pub lock_after_restore: bool,
+    #[serde(default)]
+    pub golden: Option<GoldenSnapshot>,
+}
+
+// 默认排除日志、临时文件和锁文件
+pub fn default_exclude_patterns() -> Vec<String> {
+    ["*.log", "*.tmp", "*.lock", "*.lck", "lockfile"]
+        .iter()
+        .map(|p| p.to_string())
+        .collect()
+}
+
+pub fn default_watch_debounce_secs() -> u64 {
+    5
+}
+
+pub fn default_drift_check_minutes() -> u64 {
+    10
 }
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct Config {
     pub accounts: Vec<Account>,
     pub game_config_path: PathBuf,
+    // 游戏安装目录，用于检测游戏版本；为空时使用游戏配置目录的上一级
+    #[serde(default)]
+    pub game_install_dir: Option<PathBuf>,
     pub script_paths: Vec<PathBuf>,
     pub backup_dir: PathBuf,
     pub sync_items: Vec<SyncItem>,
     pub sync_groups: Vec<SyncGroup>,
+    // 全局保留策略，适用于没有单独设置的项目、组以及游戏配置和脚本备份
+    #[serde(default)]
+    pub retention: Option<RetentionPolicy>,
+    #[serde(default)]
+    pub storage_mode: StorageMode,
+    #[serde(default)]
+    pub archive_format: ArchiveFormat,
+    // 为空时不加密
+    #[serde(default)]
+    pub encryption: Option<EncryptionConfig>,
+    // 监视模式下，源路径最后一次变化后等待多少秒再备份
+    #[serde(default = "default_watch_debounce_secs")]
+    pub watch_debounce_secs: u64,
+    #[serde(default)]
+    pub process_monitor: ProcessMonitorConfig,
+    // 守护模式下检查黄金快照漂移的间隔（分钟）
+    #[serde(default = "default_drift_check_minutes")]
+    pub drift_check_minutes: u64,
+    // 已保存的部分恢复选择
+    #[serde(default)]
+    pub restore_selections: Vec<RestoreSelection>,
+    #[serde(default)]
+    pub backup_validation: ValidationMode,
+    // 恢复时按顺序匹配最长的旧前缀
+    #[serde(default)]
+    pub path_remaps: Vec<PathRemap>,
 }
 
 impl Default for Config {
@@ -46,10 +226,21 @@ impl Default for Config {
         Config {
             accounts: Vec::new(),
             game_config_path: PathBuf::from(r"C:\Riot Games\League of Legends\Config"),
+            game_install_dir: None,
             script_paths: Vec::new(),
             backup_dir: default_backup_dir,
             sync_items: Vec::new(),
             sync_groups: Vec::new(),
+            retention: None,
+            storage_mode: StorageMode::Directory,
+            archive_format: ArchiveFormat::TarZst,
+            encryption: None,
+            watch_debounce_secs: default_watch_debounce_secs(),
+            process_monitor: ProcessMonitorConfig::default(),
+            drift_check_minutes: default_drift_check_minutes(),
+            restore_selections: Vec::new(),
+            backup_validation: ValidationMode::Warn,
+            path_remaps: Vec::new(),
         }
     }
 }
@@ -105,4 +296,9 @@ fn get_exe_dir() -> PathBuf {
 
 pub fn get_config_path() -> PathBuf {
     get_exe_dir().join("config.json")
-} 
\ No newline at end of file
+} 
+
+// 计划备份的运行状态与 config.json 放在同一目录
+pub fn get_schedule_state_path() -> PathBuf {
+    get_exe_dir().join("schedule_state.json")
+}
\ No newline at end of file
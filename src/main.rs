@@ -32,7 +32,7 @@ fn main() -> Result<()> {
             .default(0)
             .items(&options)
             .interact()?;
-            
+        
         match selection {
             0 => account_menu(&mut config)?,
             1 => sync_menu(&mut config)?,
@@ -67,7 +67,7 @@ fn account_menu(config: &mut config::Config) -> Result<()> {
         .default(0)
         .items(&options)
         .interact()?;
-        
+    
     match selection {
         0 => {
             account::list_accounts(config)?;
@@ -109,9 +109,17 @@ fn sync_menu(config: &mut config::Config) -> Result<()> {
         "删除同步项目",
         "启用/禁用同步项目",
         "为项目分配组",
+        "设置项目过滤规则",
+        "设置快照存储方式",
+        "设置快照加密",
+        "设置游戏进程监视",
+        "设置备份前校验",
+        "只读锁定",
         "组管理",
         "备份选项",
         "恢复选项",
+        "备份清理",
+        "快照管理",
         "查看备份目录",
         "设置备份目录",
         "返回主菜单",
@@ -122,7 +130,7 @@ fn sync_menu(config: &mut config::Config) -> Result<()> {
         .default(0)
         .items(&options)
         .interact()?;
-        
+    
     match selection {
         0 => {
             sync::list_sync_items(config)?;
@@ -149,15 +157,43 @@ fn sync_menu(config: &mut config::Config) -> Result<()> {
             println!("\n按回车键继续...");
             term.read_line()?;
         },
-        5 => group_menu(config)?,
-        6 => backup_menu(config)?,
-        7 => restore_menu(config)?,
+        5 => {
+            sync::filter::set_item_filters(config)?;
+            println!("\n按回车键继续...");
+            term.read_line()?;
+        },
+        6 => {
+            sync::archive::set_storage_mode(config)?;
+            println!("\n按回车键继续...");
+            term.read_line()?;
+        },
+        7 => {
+            sync::crypto::set_encryption(config)?;
+            println!("\n按回车键继续...");
+            term.read_line()?;
+        },
         8 => {
-            println!("当前备份目录: {:?}", config.backup_dir);
+            sync::process::set_process_monitor(config)?;
             println!("\n按回车键继续...");
             term.read_line()?;
         },
         9 => {
+            sync::validate::set_validation_mode(config)?;
+            println!("\n按回车键继续...");
+            term.read_line()?;
+        },
+        10 => lock_menu(config)?,
+        11 => group_menu(config)?,
+        12 => backup_menu(config)?,
+        13 => restore_menu(config)?,
+        14 => retention_menu(config)?,
+        15 => snapshot_menu(config)?,
+        16 => {
+            println!("当前备份目录: {:?}", config.backup_dir);
+            println!("\n按回车键继续...");
+            term.read_line()?;
+        },
+        17 => {
             print!("请输入新的备份目录路径: ");
             let mut input = String::new();
             std::io::stdin().read_line(&mut input)?;
@@ -172,7 +208,7 @@ fn sync_menu(config: &mut config::Config) -> Result<()> {
             println!("\n按回车键继续...");
             term.read_line()?;
         },
-        10 => return Ok(()),
+        18 => return Ok(()),
         _ => unreachable!(),
     }
     
@@ -192,6 +228,7 @@ fn group_menu(config: &mut config::Config) -> Result<()> {
         "添加同步组",
         "删除同步组",
         "启用/禁用同步组",
+        "设置组备份计划",
         "返回同步菜单",
     ];
     
@@ -200,7 +237,7 @@ fn group_menu(config: &mut config::Config) -> Result<()> {
         .default(0)
         .items(&options)
         .interact()?;
-        
+    
     match selection {
         0 => {
             sync::list_sync_groups(config)?;
@@ -222,7 +259,12 @@ fn group_menu(config: &mut config::Config) -> Result<()> {
             println!("\n按回车键继续...");
             term.read_line()?;
         },
-        4 => return Ok(()),
+        4 => {
+            sync::schedule::set_group_schedule(config)?;
+            println!("\n按回车键继续...");
+            term.read_line()?;
+        },
+        5 => return Ok(()),
         _ => unreachable!(),
     }
     
@@ -240,6 +282,9 @@ fn backup_menu(config: &mut config::Config) -> Result<()> {
     let options = vec![
         "一键备份所有启用的项目",
         "备份指定组的项目",
+        "监视模式（变化时自动备份）",
+        "守护模式（按计划自动备份）",
+        "游戏退出时自动备份",
         "返回同步菜单",
     ];
     
@@ -248,7 +293,7 @@ fn backup_menu(config: &mut config::Config) -> Result<()> {
         .default(0)
         .items(&options)
         .interact()?;
-        
+    
     match selection {
         0 => {
             sync::backup_all(config)?;
@@ -260,7 +305,22 @@ fn backup_menu(config: &mut config::Config) -> Result<()> {
             println!("\n按回车键继续...");
             term.read_line()?;
         },
-        2 => return Ok(()),
+        2 => {
+            sync::watch::watch_sync_items(config)?;
+            println!("\n按回车键继续...");
+            term.read_line()?;
+        },
+        3 => {
+            sync::schedule::run_daemon(config)?;
+            println!("\n按回车键继续...");
+            term.read_line()?;
+        },
+        4 => {
+            sync::process::backup_on_game_exit(config)?;
+            println!("\n按回车键继续...");
+            term.read_line()?;
+        },
+        5 => return Ok(()),
         _ => unreachable!(),
     }
     
@@ -278,6 +338,12 @@ fn restore_menu(config: &mut config::Config) -> Result<()> {
     let options = vec![
         "一键恢复所有启用的项目",
         "恢复指定组的项目",
+        "恢复快照集",
+        "恢复到其他路径",
+        "部分恢复（INI 节或键、JSON 路径）",
+        "删除已保存的部分恢复选择",
+        "设置路径映射",
+        "撤销上次恢复",
         "返回同步菜单",
     ];
     
@@ -286,7 +352,7 @@ fn restore_menu(config: &mut config::Config) -> Result<()> {
         .default(0)
         .items(&options)
         .interact()?;
-        
+    
     match selection {
         0 => {
             sync::restore_all(config)?;
@@ -298,9 +364,219 @@ fn restore_menu(config: &mut config::Config) -> Result<()> {
             println!("\n按回车键继续...");
             term.read_line()?;
         },
-        2 => return Ok(()),
+        2 => {
+            sync::sets::restore_set(config)?;
+            println!("\n按回车键继续...");
+            term.read_line()?;
+        },
+        3 => {
+            sync::restore_to_path(config)?;
+            println!("\n按回车键继续...");
+            term.read_line()?;
+        },
+        4 => {
+            sync::partial::partial_restore(config)?;
+            println!("\n按回车键继续...");
+            term.read_line()?;
+        },
+        5 => {
+            sync::partial::remove_restore_selection(config)?;
+            println!("\n按回车键继续...");
+            term.read_line()?;
+        },
+        6 => {
+            sync::remap::manage_path_remaps(config)?;
+            println!("\n按回车键继续...");
+            term.read_line()?;
+        },
+        7 => {
+            sync::restore::undo_last_restore(config)?;
+            println!("\n按回车键继续...");
+            term.read_line()?;
+        },
+        8 => return Ok(()),
         _ => unreachable!(),
     }
     
     Ok(())
 }
+
+// 备份清理菜单
+fn retention_menu(config: &mut config::Config) -> Result<()> {
+    let term = Term::stdout();
+    term.clear_screen()?;
+    
+    println!("备份清理");
+    println!("========");
+    
+    let options = vec![
+        "设置保留策略",
+        "固定/取消固定快照",
+        "编辑快照备注和标签",
+        "预览清理 (dry-run)",
+        "执行清理",
+        "返回同步菜单",
+    ];
+    
+    let selection = Select::with_theme(&ColorfulTheme::default())
+        .with_prompt("请选择一个选项")
+        .default(0)
+        .items(&options)
+        .interact()?;
+    
+    match selection {
+        0 => {
+            sync::retention::set_retention_policy(config)?;
+            println!("\n按回车键继续...");
+            term.read_line()?;
+        },
+        1 => {
+            sync::snapshot::toggle_pin_snapshot(config)?;
+            println!("\n按回车键继续...");
+            term.read_line()?;
+        },
+        2 => {
+            sync::snapshot::edit_snapshot_label(config)?;
+            println!("\n按回车键继续...");
+            term.read_line()?;
+        },
+        3 => {
+            sync::retention::prune_backups(config, true)?;
+            println!("\n按回车键继续...");
+            term.read_line()?;
+        },
+        4 => {
+            sync::retention::prune_backups(config, false)?;
+            println!("\n按回车键继续...");
+            term.read_line()?;
+        },
+        5 => return Ok(()),
+        _ => unreachable!(),
+    }
+    
+    Ok(())
+}
+
+// 快照管理菜单
+fn snapshot_menu(config: &mut config::Config) -> Result<()> {
+    let term = Term::stdout();
+    term.clear_screen()?;
+    
+    println!("快照管理");
+    println!("========");
+    
+    let options = vec![
+        "校验快照完整性",
+        "测试恢复快照",
+        "设置黄金快照",
+        "检查黄金快照漂移",
+        "比较快照差异",
+        "查看按键绑定",
+        "查询设置历史",
+        "浏览快照并提取文件",
+        "搜索快照",
+        "返回同步菜单",
+    ];
+    
+    let selection = Select::with_theme(&ColorfulTheme::default())
+        .with_prompt("请选择一个选项")
+        .default(0)
+        .items(&options)
+        .interact()?;
+    
+    match selection {
+        0 => {
+            sync::verify::verify_backups(config, false)?;
+            println!("\n按回车键继续...");
+            term.read_line()?;
+        },
+        1 => {
+            sync::verify::verify_backups(config, true)?;
+            println!("\n按回车键继续...");
+            term.read_line()?;
+        },
+        2 => {
+            sync::drift::set_golden_snapshot(config)?;
+            println!("\n按回车键继续...");
+            term.read_line()?;
+        },
+        3 => {
+            sync::drift::check_drift(config)?;
+            println!("\n按回车键继续...");
+            term.read_line()?;
+        },
+        4 => {
+            sync::diff::diff_snapshots(config)?;
+            println!("\n按回车键继续...");
+            term.read_line()?;
+        },
+        5 => {
+            sync::keybind::inspect_keybindings(config)?;
+            println!("\n按回车键继续...");
+            term.read_line()?;
+        },
+        6 => {
+            sync::history::settings_history(config)?;
+            println!("\n按回车键继续...");
+            term.read_line()?;
+        },
+        7 => {
+            sync::browse::browse_snapshot(config)?;
+            println!("\n按回车键继续...");
+            term.read_line()?;
+        },
+        8 => {
+            sync::search::search_snapshots(config)?;
+            println!("\n按回车键继续...");
+            term.read_line()?;
+        },
+        9 => return Ok(()),
+        _ => unreachable!(),
+    }
+    
+    Ok(())
+}
+
+// 只读锁定菜单
+fn lock_menu(config: &mut config::Config) -> Result<()> {
+    let term = Term::stdout();
+    term.clear_screen()?;
+    
+    println!("只读锁定");
+    println!("========");
+    
+    let options = vec![
+        "设置恢复后自动锁定",
+        "锁定项目",
+        "解锁项目",
+        "返回同步菜单",
+    ];
+    
+    let selection = Select::with_theme(&ColorfulTheme::default())
+        .with_prompt("请选择一个选项")
+        .default(0)
+        .items(&options)
+        .interact()?;
+    
+    match selection {
+        0 => {
+            sync::lock::toggle_lock_after_restore(config)?;
+            println!("\n按回车键继续...");
+            term.read_line()?;
+        },
+        1 => {
+            sync::lock::lock_sync_item(config)?;
+            println!("\n按回车键继续...");
+            term.read_line()?;
+        },
+        2 => {
+            sync::lock::unlock_sync_item(config)?;
+            println!("\n按回车键继续...");
+            term.read_line()?;
+        },
+        3 => return Ok(()),
+        _ => unreachable!(),
+    }
+    
+    Ok(())
+}
\ No newline at end of file
@@ -5,6 +5,9 @@ use std::path::{Path, PathBuf};
 use std::time::{SystemTime, UNIX_EPOCH};
 
 use crate::config::Config;
+use crate::sync::{crypto, index, process, remap, version};
+use crate::sync::restore::{backup_payload, RestoreSession};
+use crate::sync::snapshot::{self, Snapshot, SCRIPTS_SERIES};
 
 pub fn add_script_path(config: &mut Config) -> Result<()> {
     print!("请输入脚本文件或目录的路径: ");
@@ -71,7 +74,8 @@ pub fn backup_scripts(config: &Config) -> Result<()> {
         .unwrap()
         .as_secs();
     
-    let backup_dir = config.backup_dir.join(format!("scripts_{}", timestamp));
+    let staged = crypto::StagedSnapshot::new(config, &config.backup_dir.join(format!("{}_{}", SCRIPTS_SERIES, timestamp)))?;
+    let backup_dir = staged.path();
     fs::create_dir_all(&backup_dir)
         .with_context(|| format!("创建备份目录失败: {:?}", backup_dir))?;
     
@@ -106,6 +110,11 @@ pub fn backup_scripts(config: &Config) -> Result<()> {
         println!("已备份: {:?} -> {:?}", path, target_path);
     }
     
+    let manifest = index::scan_tree(&backup_dir)?;
+    let backup_dir = staged.finish()?;
+    snapshot::save_manifest(config, &backup_dir, manifest)?;
+    version::record_version(config, &backup_dir)?;
+    
     println!("脚本已成功备份到 {:?}", backup_dir);
     
     Ok(())
@@ -122,7 +131,7 @@ pub fn restore_scripts(config: &Config) -> Result<()> {
     // 显示带时间戳的备份
     println!("可用的脚本备份:");
     for (i, backup) in backups.iter().enumerate() {
-        println!("  {}. {:?}", i + 1, backup);
+        println!("  {}. {:?}{}", i + 1, backup.path, snapshot::describe(config, &backup.path));
     }
     
     // 让用户选择一个备份
@@ -139,12 +148,27 @@ pub fn restore_scripts(config: &Config) -> Result<()> {
         }
     };
     
-    let selected_backup = &backups[selection];
+    let selected_backup = &backups[selection].path;
+    
+    if !version::confirm_version(config, selected_backup)? {
+        println!("恢复已取消。");
+        return Ok(());
+    }
+    
+    // 客户端在运行时恢复没有意义，它退出时会覆盖恢复的文件
+    if !process::ensure_game_closed(config)? {
+        println!("恢复已取消。");
+        return Ok(());
+    }
     
     // 获取备份目录中的所有项目
-    let entries = fs::read_dir(selected_backup)
+    let content = snapshot::open_snapshot(config, selected_backup)?;
+    let entries = fs::read_dir(content.path())
         .with_context(|| format!("读取备份目录失败: {:?}", selected_backup))?;
     
+    // 先找出每个备份条目对应的脚本路径，预览映射后的位置再写入
+    let mut targets = Vec::new();
+    
     for entry in entries {
         let entry = entry?;
         let path = entry.path();
@@ -171,44 +195,28 @@ pub fn restore_scripts(config: &Config) -> Result<()> {
             continue;
         }
         
-        let target_path = &config.script_paths[index];
-        
-        // 确保父目录存在
-        if let Some(parent) = target_path.parent() {
-            fs::create_dir_all(parent)
-                .with_context(|| format!("创建父目录失败: {:?}", parent))?;
-        }
-        
-        // 如果存在，则删除现有脚本
-        if target_path.exists() {
-            if target_path.is_dir() {
-                fs::remove_dir_all(target_path).with_context(|| {
-                    format!("删除现有脚本目录失败: {:?}", target_path)
-                })?;
-            } else {
-                fs::remove_file(target_path).with_context(|| {
-                    format!("删除现有脚本文件失败: {:?}", target_path)
-                })?;
-            }
-        }
-        
-        // 从备份恢复
-        if path.is_dir() {
-            let options = CopyOptions::new();
-            copy(&path, target_path.parent().unwrap(), &options).with_context(|| {
-                format!(
-                    "从 {:?} 恢复脚本目录到 {:?} 失败",
-                    path, target_path
-                )
-            })?;
-        } else {
-            fs::copy(&path, target_path).with_context(|| {
-                format!(
-                    "从 {:?} 恢复脚本文件到 {:?} 失败",
-                    path, target_path
-                )
-            })?;
-        }
+        targets.push((path, &config.script_paths[index]));
+    }
+    
+    let labels: Vec<String> = targets.iter().map(|(path, _)| path.file_name().unwrap().to_string_lossy().to_string()).collect();
+    let preview: Vec<(&str, &Path)> = labels.iter().zip(&targets).map(|(label, (_, target))| (label.as_str(), target.as_path())).collect();
+    if !remap::confirm_targets(config, &preview)? {
+        println!("恢复已取消。");
+        return Ok(());
+    }
+    
+    let mut session = RestoreSession::begin(config);
+    
+    for (path, target_path) in &targets {
+        // 拍摄安全快照后暂存恢复，完整后再替换现有脚本
+        let payload = backup_payload(path, target_path);
+        let target_path = remap::remap_path(config, target_path);
+        session.restore(&payload, &target_path).with_context(|| {
+            format!(
+                "从 {:?} 恢复脚本到 {:?} 失败",
+                path, target_path
+            )
+        })?;
         
         println!("已恢复: {:?} -> {:?}", path, target_path);
     }
@@ -218,32 +226,7 @@ pub fn restore_scripts(config: &Config) -> Result<()> {
     Ok(())
 }
 
-fn list_script_backups(config: &Config) -> Result<Vec<PathBuf>> {
-    if !config.backup_dir.exists() {
-        return Ok(Vec::new());
-    }
-    
-    let entries = fs::read_dir(&config.backup_dir)
-        .with_context(|| format!("读取备份目录失败: {:?}", config.backup_dir))?;
-    
-    let mut backups = Vec::new();
-    
-    for entry in entries {
-        let entry = entry?;
-        let path = entry.path();
-        let file_name = path.file_name().unwrap().to_string_lossy();
-        
-        if file_name.starts_with("scripts_") && path.is_dir() {
-            backups.push(path);
-        }
-    }
-    
-    // 按修改时间排序（最新的在前）
-    backups.sort_by(|a, b| {
-        let a_meta = fs::metadata(a).unwrap();
-        let b_meta = fs::metadata(b).unwrap();
-        b_meta.modified().unwrap().cmp(&a_meta.modified().unwrap())
-    });
-    
-    Ok(backups)
-} 
\ No newline at end of file
+fn list_script_backups(config: &Config) -> Result<Vec<Snapshot>> {
+    // 最新的在前，带有标签时可以按标签筛选
+    snapshot::filter_by_tag(config, snapshot::list_snapshots(config, SCRIPTS_SERIES)?)
+}
\ No newline at end of file
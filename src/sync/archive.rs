@@ -0,0 +1,227 @@
+use anyhow::{Context, Result};
+use std::fs;
+use std::io;
+use std::path::Path;
+use zip::write::SimpleFileOptions;
+
+use crate::config::{ArchiveFormat, Config, StorageMode, SyncItem};
+use crate::sync::index::rel_to_string;
+use crate::sync::restore::remove_path;
+
+// 压缩包快照支持的扩展名
+const ARCHIVE_EXTENSIONS: [&str; 2] = ["tar.zst", "zip"];
+
+pub fn extension(format: ArchiveFormat) -> &'static str {
+    match format {
+        ArchiveFormat::TarZst => "tar.zst",
+        ArchiveFormat::Zip => "zip",
+    }
+}
+
+// 根据文件名判断压缩包格式，不是压缩包时返回 None
+pub fn format_of(path: &Path) -> Option<ArchiveFormat> {
+    let name = path.file_name()?.to_string_lossy().to_string();
+    
+    if name.ends_with(".tar.zst") {
+        Some(ArchiveFormat::TarZst)
+    } else if name.ends_with(".zip") {
+        Some(ArchiveFormat::Zip)
+    } else {
+        None
+    }
+}
+
+// 去掉快照名称中的压缩包扩展名
+pub fn strip_extension(file_name: &str) -> &str {
+    ARCHIVE_EXTENSIONS
+        .iter()
+        .find_map(|ext| file_name.strip_suffix(&format!(".{}", ext)))
+        .unwrap_or(file_name)
+}
+
+// 同步项目实际使用的存储方式：组设置优先于全局设置
+pub fn storage_mode_for(config: &Config, item: &SyncItem) -> StorageMode {
+    item.group
+        .as_ref()
+        .and_then(|g| config.sync_groups.iter().find(|group| &group.name == g))
+        .and_then(|group| group.storage_mode)
+        .unwrap_or(config.storage_mode)
+}
+
+// 把目录 src_dir 中的内容打包为 dest；先写入临时文件，完成后再改名
+pub fn create_archive(src_dir: &Path, dest: &Path, format: ArchiveFormat) -> Result<()> {
+    let tmp = dest.with_file_name(format!(
+        "{}.tmp",
+        dest.file_name().unwrap_or_default().to_string_lossy()
+    ));
+    
+    let result = match format {
+        ArchiveFormat::TarZst => write_tar_zst(src_dir, &tmp),
+        ArchiveFormat::Zip => write_zip(src_dir, &tmp),
+    };
+    
+    if let Err(e) = result {
+        let _ = remove_path(&tmp);
+        return Err(e.context(format!("创建压缩包失败: {:?}", dest)));
+    }
+    
+    fs::rename(&tmp, dest).with_context(|| format!("保存压缩包失败: {:?}", dest))?;
+    
+    Ok(())
+}
+
+fn write_tar_zst(src_dir: &Path, dest: &Path) -> Result<()> {
+    let file = fs::File::create(dest).with_context(|| format!("创建文件失败: {:?}", dest))?;
+    let encoder = zstd::Encoder::new(file, 0)?;
+    let mut builder = tar::Builder::new(encoder);
+    
+    for entry in fs::read_dir(src_dir).with_context(|| format!("读取目录失败: {:?}", src_dir))? {
+        let entry = entry?;
+        let path = entry.path();
+        
+        if path.is_dir() {
+            builder.append_dir_all(entry.file_name(), &path)?;
+        } else {
+            builder.append_path_with_name(&path, entry.file_name())?;
+        }
+    }
+    
+    let encoder = builder.into_inner()?;
+    encoder.finish()?;
+    
+    Ok(())
+}
+
+fn write_zip(src_dir: &Path, dest: &Path) -> Result<()> {
+    let file = fs::File::create(dest).with_context(|| format!("创建文件失败: {:?}", dest))?;
+    let mut writer = zip::ZipWriter::new(file);
+    let options = SimpleFileOptions::default().compression_method(zip::CompressionMethod::Deflated);
+    
+    add_zip_entries(&mut writer, src_dir, Path::new(""), options)?;
+    writer.finish()?;
+    
+    Ok(())
+}
+
+fn add_zip_entries(
+    writer: &mut zip::ZipWriter<fs::File>,
+    dir: &Path,
+    rel: &Path,
+    options: SimpleFileOptions,
+) -> Result<()> {
+    for entry in fs::read_dir(dir).with_context(|| format!("读取目录失败: {:?}", dir))? {
+        let entry = entry?;
+        let path = entry.path();
+        let child_rel = rel.join(entry.file_name());
+        let name = rel_to_string(&child_rel);
+        
+        if path.is_dir() {
+            writer.add_directory(format!("{}/", name), options)?;
+            add_zip_entries(writer, &path, &child_rel, options)?;
+        } else {
+            writer.start_file(name, options)?;
+            let mut file = fs::File::open(&path).with_context(|| format!("打开文件失败: {:?}", path))?;
+            io::copy(&mut file, writer)?;
+        }
+    }
+    
+    Ok(())
+}
+
+// 把压缩包解压到目录 dest_dir
+pub fn extract_archive(archive: &Path, dest_dir: &Path) -> Result<()> {
+    let format = format_of(archive)
+        .ok_or_else(|| anyhow::anyhow!("无法识别的压缩包格式: {:?}", archive))?;
+    let file = fs::File::open(archive).with_context(|| format!("打开压缩包失败: {:?}", archive))?;
+    
+    fs::create_dir_all(dest_dir).with_context(|| format!("创建目录失败: {:?}", dest_dir))?;
+    
+    match format {
+        ArchiveFormat::TarZst => {
+            let decoder = zstd::Decoder::new(file)?;
+            tar::Archive::new(decoder)
+                .unpack(dest_dir)
+                .with_context(|| format!("解压失败: {:?}", archive))?;
+        },
+        ArchiveFormat::Zip => {
+            zip::ZipArchive::new(file)
+                .and_then(|mut zip| zip.extract(dest_dir))
+                .with_context(|| format!("解压失败: {:?}", archive))?;
+        },
+    }
+    
+    Ok(())
+}
+
+// 设置全局或某个组的快照存储方式
+pub fn set_storage_mode(config: &mut Config) -> Result<()> {
+    let global_format = extension(config.archive_format);
+    let global_mode = match config.storage_mode {
+        StorageMode::Directory => "目录".to_string(),
+        StorageMode::Archive => format!("压缩包 ({})", global_format),
+    };
+    println!("当前全局存储方式: {}", global_mode);
+    
+    println!("要为哪一级设置存储方式?");
+    println!("  1. 全局");
+    for (i, group) in config.sync_groups.iter().enumerate() {
+        let mode = match group.storage_mode {
+            None => "使用全局设置",
+            Some(StorageMode::Directory) => "目录",
+            Some(StorageMode::Archive) => "压缩包",
+        };
+        println!("  {}. 组: {} [{}]", i + 2, group.name, mode);
+    }
+    
+    print!("请选择 (或输入0取消): ");
+    let mut input = String::new();
+    std::io::stdin().read_line(&mut input)?;
+    
+    let target: usize = match input.trim().parse() {
+        Ok(num) if num > 0 && num <= config.sync_groups.len() + 1 => num,
+        Ok(0) => return Ok(()),
+        _ => {
+            println!("无效的选择。操作已取消。");
+            return Ok(());
+        }
+    };
+    
+    println!("存储方式:");
+    println!("  1. 目录（直接复制）");
+    println!("  2. 压缩包 (tar.zst)");
+    println!("  3. 压缩包 (zip)");
+    if target > 1 {
+        println!("  4. 使用全局设置");
+    }
+    
+    print!("请选择: ");
+    let mut input = String::new();
+    std::io::stdin().read_line(&mut input)?;
+    
+    let (mode, format) = match input.trim() {
+        "1" => (Some(StorageMode::Directory), None),
+        "2" => (Some(StorageMode::Archive), Some(ArchiveFormat::TarZst)),
+        "3" => (Some(StorageMode::Archive), Some(ArchiveFormat::Zip)),
+        "4" if target > 1 => (None, None),
+        _ => {
+            println!("无效的选择。操作已取消。");
+            return Ok(());
+        }
+    };
+    
+    // 压缩格式只在全局配置中设置一次
+    if let Some(format) = format {
+        config.archive_format = format;
+    }
+    
+    if target == 1 {
+        config.storage_mode = mode.unwrap_or_default();
+    } else {
+        config.sync_groups[target - 2].storage_mode = mode;
+    }
+    
+    config.save()?;
+    println!("存储方式已更新，新的快照将使用该方式保存。");
+    
+    Ok(())
+}
\ No newline at end of file
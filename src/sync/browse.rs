@@ -0,0 +1,258 @@
+use anyhow::{Context, Result};
+use chrono::{Local, TimeZone};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::config::{Config, SyncItem};
+use crate::sync::index::{self, FileEntry};
+use crate::sync::restore::{backup_payload, copy_path, RestoreSession};
+use crate::sync::{lock, process, remap, snapshot, validate};
+
+fn format_size(size: u64) -> String {
+    if size >= 1024 * 1024 {
+        format!("{:.2} MB", size as f64 / 1024.0 / 1024.0)
+    } else if size >= 1024 {
+        format!("{:.1} KB", size as f64 / 1024.0)
+    } else {
+        format!("{} B", size)
+    }
+}
+
+fn format_mtime(millis: u64) -> String {
+    Local
+        .timestamp_millis_opt(millis as i64)
+        .single()
+        .map(|t| t.format("%Y-%m-%d %H:%M:%S").to_string())
+        .unwrap_or_else(|| "-".to_string())
+}
+
+// 按编号（逗号分隔）或相对路径选择文件；路径是文件夹时选择其中的所有文件
+fn select_files<'a>(files: &'a [FileEntry], input: &str) -> Option<Vec<&'a FileEntry>> {
+    let mut selected: Vec<&FileEntry> = Vec::new();
+    
+    for part in input.split(',').map(str::trim).filter(|p| !p.is_empty()) {
+        let matches: Vec<&FileEntry> = match part.parse::<usize>() {
+            Ok(num) if num > 0 && num <= files.len() => vec![&files[num - 1]],
+            Ok(_) => return None,
+            Err(_) => {
+                let prefix = part.replace('\\', "/").trim_matches('/').to_string();
+                let folder = format!("{}/", prefix);
+                files
+                    .iter()
+                    .filter(|f| f.path == prefix || f.path.starts_with(&folder))
+                    .collect()
+            },
+        };
+        
+        if matches.is_empty() {
+            return None;
+        }
+        for file in matches {
+            if !selected.iter().any(|f| f.path == file.path) {
+                selected.push(file);
+            }
+        }
+    }
+    
+    Some(selected)
+}
+
+// 把快照 snapshot_path 中选中的文件提取回源路径：每个文件单独拍安全快照后替换，项目中的其他文件不受影响
+pub fn extract_to_source(config: &Config, item: &SyncItem, snapshot_path: &Path, payload: &Path, paths: &[String]) -> Result<()> {
+    if !validate::confirm_restore(config, snapshot_path)? {
+        println!("提取已取消。");
+        return Ok(());
+    }
+    
+    // 客户端在运行时恢复没有意义，它退出时会覆盖恢复的文件
+    if !process::ensure_game_closed(config)? {
+        println!("提取已取消。");
+        return Ok(());
+    }
+    
+    if !remap::confirm_targets(config, &[(item.name.as_str(), item.source_path.as_path())])? {
+        println!("提取已取消。");
+        return Ok(());
+    }
+    
+    // 按路径映射表写入本机的对应位置
+    let source_path = remap::remap_path(config, &item.source_path);
+    let mut session = RestoreSession::begin(config);
+    
+    for path in paths {
+        let target = index::file_in(&source_path, path);
+        
+        // 保持目标文件原来的只读锁定状态
+        let was_locked = fs::metadata(&target).map(|m| m.permissions().readonly()).unwrap_or(false);
+        if was_locked {
+            lock::set_file_readonly(&target, false)?;
+        }
+        
+        session.restore(&index::file_in(payload, path), &target)?;
+        lock::set_file_readonly(&target, was_locked)?;
+        
+        println!("已提取: {:?}", target);
+    }
+    
+    println!("提取完成！提取前的文件已保存为安全快照，可在恢复选项中撤销上次恢复。");
+    
+    Ok(())
+}
+
+// 把选中的文件按相对路径复制到任意目录
+fn extract_to_dir(item: &SyncItem, payload: &Path, files: &[&FileEntry], dir: &Path) -> Result<()> {
+    let targets: Vec<(PathBuf, PathBuf)> = files
+        .iter()
+        .map(|file| {
+            let rel = if file.path.is_empty() {
+                item.source_path.file_name().map(PathBuf::from).unwrap_or_else(|| PathBuf::from(&item.name))
+            } else {
+                PathBuf::from(&file.path)
+            };
+            (index::file_in(payload, &file.path), dir.join(rel))
+        })
+        .collect();
+    
+    let existing = targets.iter().filter(|(_, target)| target.exists()).count();
+    if existing > 0 {
+        println!("目标目录中已有 {} 个同名文件，将被覆盖。", existing);
+        print!("请输入'yes'确认: ");
+        let mut input = String::new();
+        std::io::stdin().read_line(&mut input)?;
+        
+        if input.trim().to_lowercase() != "yes" {
+            println!("提取已取消。");
+            return Ok(());
+        }
+    }
+    
+    for (from, target) in &targets {
+        if let Some(parent) = target.parent() {
+            fs::create_dir_all(parent).with_context(|| format!("创建目录失败: {:?}", parent))?;
+        }
+        copy_path(from, target)?;
+        println!("已提取: {:?}", target);
+    }
+    
+    println!("提取完成！");
+    
+    Ok(())
+}
+
+// 浏览同步项目某个快照中的文件，并把选中的文件或文件夹提取到源路径或其他目录
+pub fn browse_snapshot(config: &Config) -> Result<()> {
+    if config.sync_items.is_empty() {
+        println!("没有同步项目。");
+        return Ok(());
+    }
+    
+    println!("同步项目列表:");
+    for (i, item) in config.sync_items.iter().enumerate() {
+        println!("  {}. {}", i + 1, item.name);
+    }
+    
+    print!("请输入项目编号 (或输入0取消): ");
+    let mut input = String::new();
+    std::io::stdin().read_line(&mut input)?;
+    
+    let item = match input.trim().parse::<usize>() {
+        Ok(num) if num > 0 && num <= config.sync_items.len() => &config.sync_items[num - 1],
+        Ok(0) => return Ok(()),
+        _ => {
+            println!("无效的选择。操作已取消。");
+            return Ok(());
+        }
+    };
+    
+    let snapshots = snapshot::filter_by_tag(config, snapshot::list_snapshots(config, &item.name)?)?;
+    if snapshots.is_empty() {
+        println!("{} 没有任何快照。", item.name);
+        return Ok(());
+    }
+    
+    println!("{} 的快照:", item.name);
+    for (i, snap) in snapshots.iter().enumerate() {
+        println!("  {}. {}{}", i + 1, snap.file_name(), snapshot::describe(config, &snap.path));
+    }
+    
+    print!("请选择快照 (或输入0取消): ");
+    let mut input = String::new();
+    std::io::stdin().read_line(&mut input)?;
+    
+    let snap = match input.trim().parse::<usize>() {
+        Ok(num) if num > 0 && num <= snapshots.len() => &snapshots[num - 1],
+        Ok(0) => return Ok(()),
+        _ => {
+            println!("无效的选择。操作已取消。");
+            return Ok(());
+        }
+    };
+    
+    // 压缩包快照会先解压到临时目录，content 在提取完成前必须保持存在
+    let content = snapshot::open_snapshot(config, &snap.path)?;
+    let payload = backup_payload(content.path(), &item.source_path);
+    
+    let files = index::scan_item_at(item, &payload)?;
+    if files.is_empty() {
+        println!("快照 {} 中没有文件。", snap.file_name());
+        return Ok(());
+    }
+    
+    println!("快照 {} 中的文件:", snap.file_name());
+    println!("  {:<5} {:<48} {:>12}  修改时间", "编号", "路径", "大小");
+    for (i, file) in files.iter().enumerate() {
+        let display = if file.path.is_empty() { item.name.as_str() } else { file.path.as_str() };
+        println!("  {:<5} {:<48} {:>12}  {}", i + 1, display, format_size(file.size), format_mtime(file.mtime));
+    }
+    
+    let total: u64 = files.iter().map(|f| f.size).sum();
+    println!("共 {} 个文件，{}。", files.len(), format_size(total));
+    
+    print!("请输入要提取的文件编号（多个用逗号分隔）或文件夹的相对路径 (直接回车返回): ");
+    let mut input = String::new();
+    std::io::stdin().read_line(&mut input)?;
+    
+    if input.trim().is_empty() {
+        return Ok(());
+    }
+    
+    let selected = match select_files(&files, &input) {
+        Some(selected) => selected,
+        None => {
+            println!("无效的选择。操作已取消。");
+            return Ok(());
+        }
+    };
+    
+    println!("已选择 {} 个文件。提取到:", selected.len());
+    println!("  1. 原位置 (只替换选中的文件)");
+    println!("  2. 其他目录");
+    
+    print!("请选择 (或输入0取消): ");
+    let mut input = String::new();
+    std::io::stdin().read_line(&mut input)?;
+    
+    match input.trim() {
+        "1" => {
+            let paths: Vec<String> = selected.iter().map(|f| f.path.clone()).collect();
+            extract_to_source(config, item, &snap.path, &payload, &paths)
+        },
+        "2" => {
+            print!("请输入目标目录: ");
+            let mut input = String::new();
+            std::io::stdin().read_line(&mut input)?;
+            
+            if input.trim().is_empty() {
+                println!("无效的目录。操作已取消。");
+                return Ok(());
+            }
+            
+            extract_to_dir(item, &payload, &selected, Path::new(input.trim()))
+        },
+        "0" => Ok(()),
+        _ => {
+            println!("无效的选择。操作已取消。");
+            Ok(())
+        }
+    }
+}
\ No newline at end of file
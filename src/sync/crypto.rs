@@ -0,0 +1,386 @@
+use anyhow::{Context, Result};
+use argon2::Argon2;
+use chacha20poly1305::aead::{Aead, Generate};
+use chacha20poly1305::{KeyInit, XChaCha20Poly1305, XNonce};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::fs;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use crate::config::{Config, EncryptionConfig};
+use crate::sync::restore::{copy_path, remove_path};
+use crate::sync::snapshot::ScratchDir;
+
+// 加密文件的开头标记，后面依次是 24 字节的随机数和密文
+const MAGIC: &[u8] = b"GTENC1\0";
+const NONCE_LEN: usize = 24;
+
+// 备份目录下的密钥校验文件，保存派生密钥用的盐和一段已知明文的密文
+const KEY_CHECK_FILE_NAME: &str = "encryption.json";
+const KEY_CHECK_PLAINTEXT: &[u8] = b"game_tool";
+
+// 派生密钥比较慢，同一个进程里缓存最近一次的结果
+static KEY_CACHE: Mutex<Option<(String, [u8; 32])>> = Mutex::new(None);
+
+#[derive(Debug, Serialize, Deserialize)]
+struct KeyCheck {
+    salt: String,
+    check: String,
+}
+
+pub struct Cipher {
+    aead: XChaCha20Poly1305,
+}
+
+impl Cipher {
+    fn from_key(key: &[u8; 32]) -> Result<Self> {
+        let aead = XChaCha20Poly1305::new_from_slice(key)
+            .map_err(|_| anyhow::anyhow!("无效的密钥长度"))?;
+        Ok(Cipher { aead })
+    }
+    
+    pub fn encrypt(&self, plaintext: &[u8]) -> Result<Vec<u8>> {
+        let nonce = XNonce::generate();
+        let ciphertext = self
+            .aead
+            .encrypt(&nonce, plaintext)
+            .map_err(|_| anyhow::anyhow!("加密失败"))?;
+        
+        let mut data = Vec::with_capacity(MAGIC.len() + NONCE_LEN + ciphertext.len());
+        data.extend_from_slice(MAGIC);
+        data.extend_from_slice(&nonce);
+        data.extend_from_slice(&ciphertext);
+        
+        Ok(data)
+    }
+    
+    pub fn decrypt(&self, data: &[u8]) -> Result<Vec<u8>> {
+        if !is_encrypted(data) || data.len() < MAGIC.len() + NONCE_LEN {
+            return Err(anyhow::anyhow!("数据不是有效的加密格式"));
+        }
+        
+        let nonce_bytes = &data[MAGIC.len()..MAGIC.len() + NONCE_LEN];
+        let nonce = XNonce::try_from(nonce_bytes).map_err(|_| anyhow::anyhow!("无效的随机数"))?;
+        
+        self.aead
+            .decrypt(&nonce, &data[MAGIC.len() + NONCE_LEN..])
+            .map_err(|_| anyhow::anyhow!("解密失败：密钥错误或数据已损坏"))
+    }
+}
+
+pub fn is_encrypted(data: &[u8]) -> bool {
+    data.starts_with(MAGIC)
+}
+
+// 只读取文件开头判断是否已加密
+pub fn file_is_encrypted(path: &Path) -> bool {
+    let mut head = [0u8; 7];
+    fs::File::open(path)
+        .and_then(|mut file| file.read_exact(&mut head))
+        .map(|_| is_encrypted(&head))
+        .unwrap_or(false)
+}
+
+// 目录中是否有任何已加密的文件
+pub fn tree_is_encrypted(path: &Path) -> bool {
+    if path.is_dir() {
+        fs::read_dir(path)
+            .map(|entries| entries.filter_map(Result::ok).any(|e| tree_is_encrypted(&e.path())))
+            .unwrap_or(false)
+    } else {
+        file_is_encrypted(path)
+    }
+}
+
+// 读取密码或密钥文件的内容
+fn read_secret(encryption: &EncryptionConfig) -> Result<Vec<u8>> {
+    if let Some(key_file) = &encryption.key_file {
+        let secret = fs::read(key_file).with_context(|| format!("读取密钥文件失败: {:?}", key_file))?;
+        if secret.is_empty() {
+            return Err(anyhow::anyhow!("密钥文件为空: {:?}", key_file));
+        }
+        return Ok(secret);
+    }
+    
+    match &encryption.passphrase {
+        Some(passphrase) if !passphrase.is_empty() => Ok(passphrase.as_bytes().to_vec()),
+        _ => Err(anyhow::anyhow!("已启用加密，但没有设置密码或密钥文件")),
+    }
+}
+
+fn derive_key(secret: &[u8], salt: &[u8]) -> Result<[u8; 32]> {
+    let mut hasher = Sha256::new();
+    hasher.update(secret);
+    hasher.update(salt);
+    let fingerprint = hex::encode(hasher.finalize());
+    
+    let mut cache = KEY_CACHE.lock().unwrap();
+    if let Some((cached, key)) = cache.as_ref() {
+        if cached == &fingerprint {
+            return Ok(*key);
+        }
+    }
+    
+    let mut key = [0u8; 32];
+    Argon2::default()
+        .hash_password_into(secret, salt, &mut key)
+        .map_err(|e| anyhow::anyhow!("派生密钥失败: {}", e))?;
+    
+    *cache = Some((fingerprint, key));
+    
+    Ok(key)
+}
+
+// 按配置加载密钥并用备份目录中的校验文件确认密钥正确；未启用加密时返回 None
+pub fn load_cipher(config: &Config) -> Result<Option<Cipher>> {
+    let encryption = match &config.encryption {
+        Some(encryption) => encryption,
+        None => return Ok(None),
+    };
+    
+    let secret = read_secret(encryption)?;
+    let check_path = config.backup_dir.join(KEY_CHECK_FILE_NAME);
+    
+    if !check_path.exists() {
+        // 第一次使用时生成盐和校验数据
+        let salt = <[u8; 16]>::generate();
+        let cipher = Cipher::from_key(&derive_key(&secret, &salt)?)?;
+        let key_check = KeyCheck {
+            salt: hex::encode(salt),
+            check: hex::encode(cipher.encrypt(KEY_CHECK_PLAINTEXT)?),
+        };
+        
+        config.ensure_backup_dir()?;
+        let check_str = serde_json::to_string_pretty(&key_check)
+            .with_context(|| "序列化密钥校验数据失败")?;
+        fs::write(&check_path, check_str)
+            .with_context(|| format!("写入密钥校验文件失败: {:?}", check_path))?;
+        
+        return Ok(Some(cipher));
+    }
+    
+    let check_str = fs::read_to_string(&check_path)
+        .with_context(|| format!("读取密钥校验文件失败: {:?}", check_path))?;
+    let key_check: KeyCheck = serde_json::from_str(&check_str)
+        .with_context(|| format!("解析密钥校验文件失败: {:?}", check_path))?;
+    let salt = hex::decode(&key_check.salt).with_context(|| "密钥校验文件中的盐无效")?;
+    let check = hex::decode(&key_check.check).with_context(|| "密钥校验文件中的校验数据无效")?;
+    
+    let cipher = Cipher::from_key(&derive_key(&secret, &salt)?)?;
+    match cipher.decrypt(&check) {
+        Ok(plaintext) if plaintext == KEY_CHECK_PLAINTEXT => Ok(Some(cipher)),
+        _ => Err(anyhow::anyhow!(
+            "密钥错误：当前密码或密钥文件与备份目录 {:?} 中加密数据使用的不一致",
+            config.backup_dir
+        )),
+    }
+}
+
+// 需要解密时加载密钥；没有配置密钥时给出明确的错误
+fn require_cipher(config: &Config, path: &Path) -> Result<Cipher> {
+    load_cipher(config)?
+        .ok_or_else(|| anyhow::anyhow!("{:?} 已加密，但配置中没有设置密码或密钥文件", path))
+}
+
+// 读取备份目录中可能已加密的文件
+pub fn read_file(config: &Config, path: &Path) -> Result<Vec<u8>> {
+    let data = fs::read(path).with_context(|| format!("读取文件失败: {:?}", path))?;
+    
+    if !is_encrypted(&data) {
+        return Ok(data);
+    }
+    
+    require_cipher(config, path)?
+        .decrypt(&data)
+        .with_context(|| format!("解密 {:?} 失败", path))
+}
+
+pub fn read_to_string(config: &Config, path: &Path) -> Result<String> {
+    String::from_utf8(read_file(config, path)?)
+        .with_context(|| format!("文件不是有效的 UTF-8 文本: {:?}", path))
+}
+
+// 写入备份目录中的文件，启用加密时写入密文
+pub fn write_file(config: &Config, path: &Path, data: &[u8]) -> Result<()> {
+    let data = match load_cipher(config)? {
+        Some(cipher) => cipher.encrypt(data)?,
+        None => data.to_vec(),
+    };
+    
+    fs::write(path, data).with_context(|| format!("写入文件失败: {:?}", path))?;
+    
+    Ok(())
+}
+
+// 就地加密文件或目录中的所有文件（已加密的文件跳过）
+pub fn encrypt_tree(cipher: &Cipher, path: &Path) -> Result<()> {
+    if path.is_dir() {
+        for entry in fs::read_dir(path).with_context(|| format!("读取目录失败: {:?}", path))? {
+            encrypt_tree(cipher, &entry?.path())?;
+        }
+        return Ok(());
+    }
+    
+    let data = fs::read(path).with_context(|| format!("读取文件失败: {:?}", path))?;
+    if is_encrypted(&data) {
+        return Ok(());
+    }
+    
+    // 先写入临时文件再替换，避免中途失败留下半个文件
+    let tmp = path.with_file_name(format!(
+        "{}.enc-tmp",
+        path.file_name().unwrap_or_default().to_string_lossy()
+    ));
+    fs::write(&tmp, cipher.encrypt(&data)?)
+        .with_context(|| format!("写入加密文件失败: {:?}", tmp))?;
+    fs::rename(&tmp, path).with_context(|| format!("替换加密文件失败: {:?}", path))?;
+    
+    Ok(())
+}
+
+// 复制文件或目录，同时解密其中已加密的文件
+pub fn decrypt_copy(config: &Config, from: &Path, to: &Path) -> Result<()> {
+    if from.is_dir() {
+        fs::create_dir_all(to).with_context(|| format!("创建目录失败: {:?}", to))?;
+        for entry in fs::read_dir(from).with_context(|| format!("读取目录失败: {:?}", from))? {
+            let entry = entry?;
+            decrypt_copy(config, &entry.path(), &to.join(entry.file_name()))?;
+        }
+        return Ok(());
+    }
+    
+    let data = read_file(config, from)?;
+    fs::write(to, data).with_context(|| format!("写入文件失败: {:?}", to))?;
+    
+    Ok(())
+}
+
+// 正在创建的快照：启用加密时内容先写入备份目录之外的临时目录，加密后才移入备份目录，
+// 这样云同步客户端不会上传明文，加密中途失败也不会在备份目录中留下明文
+pub struct StagedSnapshot {
+    dest: PathBuf,
+    staging: Option<(Cipher, ScratchDir)>,
+}
+
+impl StagedSnapshot {
+    pub fn new(config: &Config, dest: &Path) -> Result<Self> {
+        let staging = match load_cipher(config)? {
+            Some(cipher) => {
+                let scratch = ScratchDir::new("encrypt");
+                fs::create_dir_all(scratch.path())
+                    .with_context(|| format!("创建临时目录失败: {:?}", scratch.path()))?;
+                Some((cipher, scratch))
+            },
+            None => None,
+        };
+        
+        Ok(StagedSnapshot {
+            dest: dest.to_path_buf(),
+            staging,
+        })
+    }
+    
+    // 快照内容的写入位置
+    pub fn path(&self) -> PathBuf {
+        match &self.staging {
+            Some((_, scratch)) => scratch.path().join(self.dest.file_name().unwrap_or_default()),
+            None => self.dest.clone(),
+        }
+    }
+    
+    // 加密临时目录中的内容并移入备份目录，返回快照在备份目录中的路径
+    pub fn finish(self) -> Result<PathBuf> {
+        let (cipher, scratch) = match &self.staging {
+            Some(staging) => staging,
+            None => return Ok(self.dest),
+        };
+        
+        let staged = scratch.path().join(self.dest.file_name().unwrap_or_default());
+        encrypt_tree(cipher, &staged).with_context(|| format!("加密快照失败: {:?}", self.dest))?;
+        
+        // 临时目录通常在另一个分区上，无法直接改名时复制过去；复制失败时删除不完整的快照
+        if fs::rename(&staged, &self.dest).is_err() {
+            if let Err(e) = copy_path(&staged, &self.dest) {
+                let _ = remove_path(&self.dest);
+                return Err(e.context(format!("移动加密快照到 {:?} 失败", self.dest)));
+            }
+        }
+        
+        Ok(self.dest)
+    }
+}
+
+// 设置备份目录的加密方式
+pub fn set_encryption(config: &mut Config) -> Result<()> {
+    let status = match &config.encryption {
+        None => "未启用".to_string(),
+        Some(e) if e.key_file.is_some() => format!("密钥文件 {:?}", e.key_file.as_ref().unwrap()),
+        Some(_) => "密码".to_string(),
+    };
+    println!("当前加密方式: {}", status);
+    println!("注意: 已经加密的快照仍然需要原来的密码或密钥文件才能读取。");
+    
+    println!("  1. 使用密码加密");
+    println!("  2. 使用密钥文件加密");
+    println!("  3. 关闭加密（之后的新快照不再加密）");
+    
+    print!("请选择 (或输入0取消): ");
+    let mut input = String::new();
+    std::io::stdin().read_line(&mut input)?;
+    
+    let encryption = match input.trim() {
+        "1" => {
+            print!("请输入密码: ");
+            let mut passphrase = String::new();
+            std::io::stdin().read_line(&mut passphrase)?;
+            let passphrase = passphrase.trim_end_matches(['\r', '\n']).to_string();
+            
+            if passphrase.is_empty() {
+                println!("密码不能为空。操作已取消。");
+                return Ok(());
+            }
+            
+            Some(EncryptionConfig {
+                passphrase: Some(passphrase),
+                key_file: None,
+            })
+        },
+        "2" => {
+            print!("请输入密钥文件路径: ");
+            let mut path = String::new();
+            std::io::stdin().read_line(&mut path)?;
+            let path = std::path::PathBuf::from(path.trim());
+            
+            if !path.is_file() {
+                println!("密钥文件不存在: {:?}", path);
+                return Ok(());
+            }
+            
+            Some(EncryptionConfig {
+                passphrase: None,
+                key_file: Some(path),
+            })
+        },
+        "3" => None,
+        "0" => return Ok(()),
+        _ => {
+            println!("无效的选择。操作已取消。");
+            return Ok(());
+        }
+    };
+    
+    // 保存前确认新密钥与备份目录中已有的加密数据一致
+    let previous = std::mem::replace(&mut config.encryption, encryption);
+    if let Err(e) = load_cipher(config) {
+        config.encryption = previous;
+        println!("无法使用新的密钥: {:#}", e);
+        return Ok(());
+    }
+    
+    config.save()?;
+    println!("加密设置已更新。");
+    
+    Ok(())
+}
\ No newline at end of file
@@ -0,0 +1,269 @@
+use anyhow::Result;
+use similar::TextDiff;
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::config::{Config, SyncItem};
+use crate::sync::index::{self, FileEntry};
+use crate::sync::restore::backup_payload;
+use crate::sync::settings;
+use crate::sync::snapshot::{self, Snapshot, SnapshotContent};
+
+// 两个版本之间键级别的差异
+#[derive(Debug, Default)]
+pub struct KeyDiff {
+    pub added: Vec<(String, String)>,
+    pub removed: Vec<(String, String)>,
+    // 键、旧值、新值
+    pub modified: Vec<(String, String, String)>,
+}
+
+impl KeyDiff {
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty() && self.modified.is_empty()
+    }
+}
+
+pub fn diff_keys(old: &[(String, String)], new: &[(String, String)]) -> KeyDiff {
+    let old_map: HashMap<&str, &str> = old.iter().map(|(k, v)| (k.as_str(), v.as_str())).collect();
+    let new_map: HashMap<&str, &str> = new.iter().map(|(k, v)| (k.as_str(), v.as_str())).collect();
+    
+    let mut diff = KeyDiff::default();
+    
+    for (key, value) in old {
+        match new_map.get(key.as_str()) {
+            None => diff.removed.push((key.clone(), value.clone())),
+            Some(new_value) if *new_value != value => {
+                diff.modified.push((key.clone(), value.clone(), new_value.to_string()));
+            },
+            Some(_) => {},
+        }
+    }
+    
+    for (key, value) in new {
+        if !old_map.contains_key(key.as_str()) {
+            diff.added.push((key.clone(), value.clone()));
+        }
+    }
+    
+    diff
+}
+
+// 对比的一方：快照或当前源路径
+struct Side {
+    label: String,
+    root: PathBuf,
+    // 快照内容（可能是临时解压目录），比较完成前必须保持存在
+    _content: Option<SnapshotContent>,
+}
+
+impl Side {
+    fn snapshot(config: &Config, item: &SyncItem, snapshot: &Snapshot) -> Result<Self> {
+        let content = snapshot::open_snapshot(config, &snapshot.path)?;
+        let root = backup_payload(content.path(), &item.source_path);
+        
+        Ok(Side {
+            label: snapshot.file_name(),
+            root,
+            _content: Some(content),
+        })
+    }
+    
+    fn live(item: &SyncItem) -> Self {
+        Side {
+            label: "当前源路径".to_string(),
+            root: item.source_path.clone(),
+            _content: None,
+        }
+    }
+    
+    // 文件在这一方的实际路径；单文件项目的相对路径为空
+    fn file(&self, rel: &str) -> PathBuf {
+        if rel.is_empty() {
+            self.root.clone()
+        } else {
+            self.root.join(rel)
+        }
+    }
+}
+
+pub fn print_key_diff(diff: &KeyDiff) {
+    for (key, value) in &diff.removed {
+        println!("    - {} = {}", key, value);
+    }
+    for (key, value) in &diff.added {
+        println!("    + {} = {}", key, value);
+    }
+    for (key, old, new) in &diff.modified {
+        println!("    ~ {}: {} -> {}", key, old, new);
+    }
+}
+
+fn print_text_diff(old: &str, new: &str, old_label: &str, new_label: &str) {
+    let diff = TextDiff::from_lines(old, new);
+    let unified = diff
+        .unified_diff()
+        .context_radius(2)
+        .header(old_label, new_label)
+        .to_string();
+    
+    for line in unified.lines() {
+        println!("    {}", line);
+    }
+}
+
+// 比较同一文件的两个版本：能解析的格式按键比较，否则按行比较
+fn diff_file(display: &str, old_path: &Path, new_path: &Path, old: &Side, new: &Side) {
+    let (old_bytes, new_bytes) = match (fs::read(old_path), fs::read(new_path)) {
+        (Ok(a), Ok(b)) => (a, b),
+        (Err(e), _) | (_, Err(e)) => {
+            println!("  {}: 读取失败: {}", display, e);
+            return;
+        },
+    };
+    
+    let (old_text, new_text) = match (String::from_utf8(old_bytes), String::from_utf8(new_bytes)) {
+        (Ok(a), Ok(b)) => (a, b),
+        _ => {
+            println!("  {}: 二进制文件不同", display);
+            return;
+        },
+    };
+    
+    let format = settings::format_of(new_path);
+    
+    match (settings::flatten(format, &old_text), settings::flatten(format, &new_text)) {
+        (Ok(Some(old_keys)), Ok(Some(new_keys))) => {
+            let diff = diff_keys(&old_keys, &new_keys);
+            if diff.is_empty() {
+                // 只有注释、空白或顺序变化
+                println!("  {}: 键值没有变化（仅格式或注释不同）", display);
+            } else {
+                println!(
+                    "  {}: 新增 {} 个，删除 {} 个，修改 {} 个键",
+                    display,
+                    diff.added.len(),
+                    diff.removed.len(),
+                    diff.modified.len()
+                );
+                print_key_diff(&diff);
+            }
+        },
+        (Ok(None), _) | (_, Ok(None)) => {
+            println!("  {}:", display);
+            print_text_diff(&old_text, &new_text, &old.label, &new.label);
+        },
+        (Err(e), _) | (_, Err(e)) => {
+            println!("  {}: 无法按 {:?} 格式解析 ({:#})，改为按行比较:", display, format, e);
+            print_text_diff(&old_text, &new_text, &old.label, &new.label);
+        },
+    }
+}
+
+fn compare(item: &SyncItem, old: &Side, new: &Side) -> Result<()> {
+    let old_files = index::scan_item_at(item, &old.root)?;
+    let new_files = index::scan_item_at(item, &new.root)?;
+    let old_map: HashMap<&str, &FileEntry> = old_files.iter().map(|f| (f.path.as_str(), f)).collect();
+    let new_map: HashMap<&str, &FileEntry> = new_files.iter().map(|f| (f.path.as_str(), f)).collect();
+    
+    println!("比较 {}: {} -> {}", item.name, old.label, new.label);
+    
+    let mut changed = 0;
+    
+    for entry in &old_files {
+        let display = if entry.path.is_empty() { item.name.as_str() } else { entry.path.as_str() };
+        
+        match new_map.get(entry.path.as_str()) {
+            None => {
+                println!("  {}: 已删除", display);
+                changed += 1;
+            },
+            Some(new_entry) if new_entry.hash != entry.hash => {
+                diff_file(display, &old.file(&entry.path), &new.file(&entry.path), old, new);
+                changed += 1;
+            },
+            Some(_) => {},
+        }
+    }
+    
+    for entry in &new_files {
+        if !old_map.contains_key(entry.path.as_str()) {
+            println!("  {}: 新增", entry.path);
+            changed += 1;
+        }
+    }
+    
+    if changed == 0 {
+        println!("  两者内容相同。");
+    }
+    
+    Ok(())
+}
+
+fn read_choice(prompt: &str) -> Result<Option<usize>> {
+    print!("{}", prompt);
+    let mut input = String::new();
+    std::io::stdin().read_line(&mut input)?;
+    
+    Ok(input.trim().parse().ok())
+}
+
+// 比较同步项目的两个快照，或一个快照与当前源路径
+pub fn diff_snapshots(config: &Config) -> Result<()> {
+    if config.sync_items.is_empty() {
+        println!("没有同步项目。");
+        return Ok(());
+    }
+    
+    println!("同步项目列表:");
+    for (i, item) in config.sync_items.iter().enumerate() {
+        println!("  {}. {}", i + 1, item.name);
+    }
+    
+    let item = match read_choice("请输入项目编号 (或输入0取消): ")? {
+        Some(num) if num > 0 && num <= config.sync_items.len() => &config.sync_items[num - 1],
+        Some(0) => return Ok(()),
+        _ => {
+            println!("无效的选择。操作已取消。");
+            return Ok(());
+        }
+    };
+    
+    let snapshots = snapshot::filter_by_tag(config, snapshot::list_snapshots(config, &item.name)?)?;
+    if snapshots.is_empty() {
+        println!("{} 没有任何快照。", item.name);
+        return Ok(());
+    }
+    
+    println!("{} 的快照:", item.name);
+    for (i, snap) in snapshots.iter().enumerate() {
+        println!("  {}. {}{}", i + 1, snap.file_name(), snapshot::describe(config, &snap.path));
+    }
+    
+    let old = match read_choice("请选择较旧的快照 (或输入0取消): ")? {
+        Some(num) if num > 0 && num <= snapshots.len() => &snapshots[num - 1],
+        Some(0) => return Ok(()),
+        _ => {
+            println!("无效的选择。操作已取消。");
+            return Ok(());
+        }
+    };
+    
+    let new = match read_choice("请选择较新的快照 (输入0与当前源路径比较): ")? {
+        Some(0) => None,
+        Some(num) if num <= snapshots.len() => Some(&snapshots[num - 1]),
+        _ => {
+            println!("无效的选择。操作已取消。");
+            return Ok(());
+        }
+    };
+    
+    let old_side = Side::snapshot(config, item, old)?;
+    let new_side = match new {
+        Some(snap) => Side::snapshot(config, item, snap)?,
+        None => Side::live(item),
+    };
+    
+    compare(item, &old_side, &new_side)
+}
\ No newline at end of file
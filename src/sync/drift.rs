@@ -0,0 +1,295 @@
+use anyhow::Result;
+use std::collections::HashMap;
+use std::path::PathBuf;
+
+use crate::config::{Config, GoldenSnapshot, SyncItem};
+use crate::sync::index::{self, FileEntry};
+use crate::sync::process::{self, ProcessProbe, SystemProbe};
+use crate::sync::restore::{backup_payload, RestoreSession};
+use crate::sync::watch::log;
+use crate::sync::{restore_item, snapshot, validate};
+
+// 后台自动修复前的安全快照存放在备份目录下的该子目录中
+const HEAL_DIR_NAME: &str = "pre_heal";
+
+pub enum DriftOutcome {
+    NoDrift,
+    // 只报告，没有修复
+    Reported(Vec<String>),
+    Healed(Vec<String>),
+    // 游戏正在运行，推迟修复
+    Deferred(Vec<String>),
+}
+
+fn golden_path(config: &Config, golden: &GoldenSnapshot) -> PathBuf {
+    config.backup_dir.join(&golden.snapshot)
+}
+
+// 比较源路径与黄金快照，返回每个不一致文件的说明；没有设置黄金快照时返回 None
+pub fn detect_drift(config: &Config, item: &SyncItem) -> Result<Option<Vec<String>>> {
+    let golden = match &item.golden {
+        Some(golden) => golden,
+        None => return Ok(None),
+    };
+    
+    let path = golden_path(config, golden);
+    if !path.exists() {
+        return Err(anyhow::anyhow!("{} 的黄金快照不存在: {:?}", item.name, path));
+    }
+    
+    let content = snapshot::open_snapshot(config, &path)?;
+    let payload = backup_payload(content.path(), &item.source_path);
+    
+    let expected = index::scan_item_at(item, &payload)?;
+    let actual = index::scan_item_at(item, &item.source_path)?;
+    let actual: HashMap<&str, &FileEntry> = actual.iter().map(|f| (f.path.as_str(), f)).collect();
+    
+    let mut drift = Vec::new();
+    
+    for entry in &expected {
+        let name = if entry.path.is_empty() { item.name.as_str() } else { entry.path.as_str() };
+        match actual.get(entry.path.as_str()) {
+            None => drift.push(format!("缺失: {}", name)),
+            Some(live) if live.size != entry.size || live.hash != entry.hash => {
+                drift.push(format!("已修改: {}", name));
+            },
+            Some(_) => {},
+        }
+    }
+    
+    for path in actual.keys() {
+        if !expected.iter().any(|e| e.path == *path) {
+            drift.push(format!("多余: {}", path));
+        }
+    }
+    
+    drift.sort();
+    
+    Ok(Some(drift))
+}
+
+// 用黄金快照覆盖源路径；恢复前的内容保存在 session 的安全快照中
+fn heal(session: &mut RestoreSession, item: &SyncItem) -> Result<()> {
+    let golden = match &item.golden {
+        Some(golden) => golden,
+        None => return Ok(()),
+    };
+    
+    let path = golden_path(session.config(), golden);
+    restore_item(session, item, &path)
+}
+
+// 检查一个项目；设置了自动修复且 allow_heal 时恢复黄金快照
+pub fn check_item(config: &Config, item: &SyncItem, allow_heal: bool) -> Result<DriftOutcome> {
+    let drift = match detect_drift(config, item)? {
+        Some(drift) if !drift.is_empty() => drift,
+        _ => return Ok(DriftOutcome::NoDrift),
+    };
+    
+    let auto_heal = item.golden.as_ref().map(|g| g.auto_heal).unwrap_or(false);
+    if !auto_heal {
+        return Ok(DriftOutcome::Reported(drift));
+    }
+    if !allow_heal {
+        return Ok(DriftOutcome::Deferred(drift));
+    }
+    
+    // 后台修复的安全快照单独存放，不覆盖用户可以撤销的上一次手动恢复
+    let mut session = RestoreSession::begin_at(config, config.backup_dir.join(HEAL_DIR_NAME));
+    heal(&mut session, item)?;
+    
+    Ok(DriftOutcome::Healed(drift))
+}
+
+fn game_running(config: &Config) -> bool {
+    !SystemProbe::default()
+        .running(&config.process_monitor.process_names)
+        .is_empty()
+}
+
+// 后台检查（守护模式和监视模式）：结果写入日志，游戏运行时不修复；返回是否修复了文件
+pub fn check_and_log(config: &Config, item: &SyncItem) -> bool {
+    let outcome = match check_item(config, item, !game_running(config)) {
+        Ok(outcome) => outcome,
+        Err(e) => {
+            log(&format!("检查 {} 的漂移失败: {:#}", item.name, e));
+            return false;
+        },
+    };
+    
+    match outcome {
+        DriftOutcome::NoDrift => false,
+        DriftOutcome::Reported(drift) => {
+            log(&format!("{} 与黄金快照不一致: {}", item.name, drift.join(", ")));
+            false
+        },
+        DriftOutcome::Deferred(drift) => {
+            log(&format!("{} 与黄金快照不一致，游戏正在运行，推迟修复: {}", item.name, drift.join(", ")));
+            false
+        },
+        DriftOutcome::Healed(drift) => {
+            log(&format!(
+                "已按黄金快照修复 {}: {} (修复前的内容保存在 {:?})",
+                item.name,
+                drift.join(", "),
+                config.backup_dir.join(HEAL_DIR_NAME)
+            ));
+            true
+        },
+    }
+}
+
+// 设置了黄金快照的项目
+pub fn golden_items(config: &Config) -> Vec<&SyncItem> {
+    config
+        .sync_items
+        .iter()
+        .filter(|item| item.is_enabled && item.golden.is_some())
+        .collect()
+}
+
+// 立即检查所有设置了黄金快照的项目
+pub fn check_drift(config: &Config) -> Result<()> {
+    let items = golden_items(config);
+    if items.is_empty() {
+        println!("没有设置了黄金快照的启用项目。");
+        return Ok(());
+    }
+    
+    let mut checked = Vec::new();
+    for item in &items {
+        let drift = detect_drift(config, item)?.unwrap_or_default();
+        if drift.is_empty() {
+            println!("{}: 与黄金快照一致", item.name);
+            continue;
+        }
+        
+        println!("{}: 与黄金快照不一致", item.name);
+        for line in &drift {
+            println!("  {}", line);
+        }
+        checked.push((*item, drift));
+    }
+    
+    let to_heal: Vec<_> = checked
+        .into_iter()
+        .filter(|(item, _)| item.golden.as_ref().map(|g| g.auto_heal).unwrap_or(false))
+        .collect();
+    
+    if to_heal.is_empty() {
+        return Ok(());
+    }
+    
+    // 客户端在运行时修复没有意义，它退出时会再次覆盖
+    if !process::ensure_game_closed(config)? {
+        println!("修复已取消。");
+        return Ok(());
+    }
+    
+    let mut session = RestoreSession::begin(config);
+    for (item, drift) in to_heal {
+        heal(&mut session, item)?;
+        println!("已按黄金快照修复 {} ({} 个文件)", item.name, drift.len());
+    }
+    println!("修复前的内容已保存为安全快照，可在恢复选项中撤销上次恢复。");
+    
+    Ok(())
+}
+
+// 把项目的某个快照设为黄金快照，并选择发现漂移时只报告还是自动修复
+pub fn set_golden_snapshot(config: &mut Config) -> Result<()> {
+    if config.sync_items.is_empty() {
+        println!("没有同步项目。");
+        return Ok(());
+    }
+    
+    println!("同步项目列表:");
+    for (i, item) in config.sync_items.iter().enumerate() {
+        let golden = match &item.golden {
+            Some(golden) if golden.auto_heal => format!("黄金快照: {}, 自动修复", golden.snapshot),
+            Some(golden) => format!("黄金快照: {}, 只报告", golden.snapshot),
+            None => "未设置黄金快照".to_string(),
+        };
+        println!("  {}. {} [{}]", i + 1, item.name, golden);
+    }
+    
+    print!("请输入项目编号 (或输入0取消): ");
+    let mut input = String::new();
+    std::io::stdin().read_line(&mut input)?;
+    
+    let item_idx: usize = match input.trim().parse() {
+        Ok(num) if num > 0 && num <= config.sync_items.len() => num - 1,
+        Ok(0) => return Ok(()),
+        _ => {
+            println!("无效的选择。操作已取消。");
+            return Ok(());
+        }
+    };
+    
+    let item_name = config.sync_items[item_idx].name.clone();
+    let snapshots = snapshot::filter_by_tag(config, snapshot::list_snapshots(config, &item_name)?)?;
+    
+    println!("{} 的快照:", item_name);
+    println!("  0. 取消黄金快照");
+    for (i, snap) in snapshots.iter().enumerate() {
+        println!("  {}. {}{}", i + 1, snap.file_name(), snapshot::describe(config, &snap.path));
+    }
+    
+    print!("请选择快照: ");
+    let mut input = String::new();
+    std::io::stdin().read_line(&mut input)?;
+    
+    let snapshot_name = match input.trim().parse::<usize>() {
+        Ok(0) => None,
+        Ok(num) if num <= snapshots.len() => {
+            // 黄金快照会被自动恢复，可疑的快照同样需要强制确认
+            if !validate::confirm_restore(config, &snapshots[num - 1].path)? {
+                println!("操作已取消。");
+                return Ok(());
+            }
+            Some(snapshots[num - 1].file_name())
+        },
+        _ => {
+            println!("无效的选择。操作已取消。");
+            return Ok(());
+        }
+    };
+    
+    let golden = match snapshot_name {
+        Some(snapshot) => {
+            println!("发现漂移时:");
+            println!("  1. 只报告");
+            println!("  2. 自动恢复黄金快照");
+            
+            print!("请选择: ");
+            let mut input = String::new();
+            std::io::stdin().read_line(&mut input)?;
+            
+            let auto_heal = match input.trim() {
+                "1" => false,
+                "2" => true,
+                _ => {
+                    println!("无效的选择。操作已取消。");
+                    return Ok(());
+                }
+            };
+            
+            Some(GoldenSnapshot { snapshot, auto_heal })
+        },
+        None => None,
+    };
+    
+    let description = golden.clone();
+    config.sync_items[item_idx].golden = golden;
+    config.save()?;
+    
+    match &description {
+        Some(golden) => println!(
+            "已将 {} 设为 {} 的黄金快照。可立即检查，也会在守护模式和监视模式中自动检查。",
+            golden.snapshot, item_name
+        ),
+        None => println!("已取消 {} 的黄金快照。", item_name),
+    }
+    
+    Ok(())
+}
\ No newline at end of file
@@ -0,0 +1,187 @@
+use anyhow::{Context, Result};
+use ignore::gitignore::{Gitignore, GitignoreBuilder};
+use std::fs;
+use std::path::Path;
+
+use crate::config::{default_exclude_patterns, Config, SyncItem};
+use crate::sync::restore::copy_path;
+
+// 同步项目的包含/排除规则，路径均相对于项目的源目录
+pub struct PathFilter {
+    include: Option<Gitignore>,
+    exclude: Gitignore,
+}
+
+impl PathFilter {
+    pub fn new(include: &[String], exclude: &[String]) -> Result<Self> {
+        let include = if include.is_empty() {
+            None
+        } else {
+            Some(build_matcher(include)?)
+        };
+        
+        Ok(PathFilter {
+            include,
+            exclude: build_matcher(exclude)?,
+        })
+    }
+    
+    pub fn for_item(item: &SyncItem) -> Result<Self> {
+        Self::new(&item.include, &item.exclude)
+            .with_context(|| format!("同步项目 {} 的过滤规则无效", item.name))
+    }
+    
+    // 判断相对路径是否参与备份和恢复；目录只要没有被排除就会继续向下检查
+    pub fn is_included(&self, rel: &Path, is_dir: bool) -> bool {
+        if self.exclude.matched_path_or_any_parents(rel, is_dir).is_ignore() {
+            return false;
+        }
+        
+        match &self.include {
+            None => true,
+            Some(include) => is_dir || include.matched_path_or_any_parents(rel, is_dir).is_ignore(),
+        }
+    }
+}
+
+fn build_matcher(patterns: &[String]) -> Result<Gitignore> {
+    let mut builder = GitignoreBuilder::new("");
+    
+    for pattern in patterns {
+        builder
+            .add_line(None, pattern)
+            .with_context(|| format!("无效的过滤规则: {}", pattern))?;
+    }
+    
+    builder.build().with_context(|| "构建过滤规则失败")
+}
+
+// 按过滤规则把目录 from 复制为 to，只复制被包含的文件
+pub fn copy_filtered(from: &Path, to: &Path, filter: &PathFilter) -> Result<()> {
+    copy_matching(from, to, Path::new(""), filter, true)
+}
+
+// 只复制被过滤规则排除的文件，恢复时用它保留现有的被排除文件
+pub fn copy_excluded(from: &Path, to: &Path, filter: &PathFilter) -> Result<()> {
+    copy_matching(from, to, Path::new(""), filter, false)
+}
+
+fn copy_matching(
+    from: &Path,
+    to: &Path,
+    rel: &Path,
+    filter: &PathFilter,
+    want_included: bool,
+) -> Result<()> {
+    // 复制被包含的内容时保留空目录；复制被排除的文件时只按需创建目录
+    if want_included {
+        fs::create_dir_all(to).with_context(|| format!("创建目录失败: {:?}", to))?;
+    }
+    
+    let entries = fs::read_dir(from).with_context(|| format!("读取目录失败: {:?}", from))?;
+    
+    for entry in entries {
+        let entry = entry?;
+        let path = entry.path();
+        let child_rel = rel.join(entry.file_name());
+        let child_to = to.join(entry.file_name());
+        let is_dir = path.is_dir();
+        let included = filter.is_included(&child_rel, is_dir);
+        
+        if is_dir {
+            if included {
+                copy_matching(&path, &child_to, &child_rel, filter, want_included)?;
+            } else if !want_included {
+                // 被排除的目录里的所有内容都属于被排除的文件
+                fs::create_dir_all(to).with_context(|| format!("创建目录失败: {:?}", to))?;
+                copy_path(&path, &child_to)?;
+            }
+        } else if included == want_included {
+            fs::create_dir_all(to).with_context(|| format!("创建目录失败: {:?}", to))?;
+            fs::copy(&path, &child_to)
+                .with_context(|| format!("从 {:?} 复制到 {:?} 失败", path, child_to))?;
+        }
+    }
+    
+    Ok(())
+}
+
+// 读取逗号分隔的规则列表
+fn read_patterns(prompt: &str) -> Result<Option<Vec<String>>> {
+    print!("{}: ", prompt);
+    let mut input = String::new();
+    std::io::stdin().read_line(&mut input)?;
+    
+    let input = input.trim();
+    if input.is_empty() {
+        return Ok(None);
+    }
+    
+    if input == "-" {
+        return Ok(Some(Vec::new()));
+    }
+    
+    Ok(Some(
+        input
+            .split(',')
+            .map(|p| p.trim().to_string())
+            .filter(|p| !p.is_empty())
+            .collect(),
+    ))
+}
+
+// 设置同步项目的包含/排除规则
+pub fn set_item_filters(config: &mut Config) -> Result<()> {
+    if config.sync_items.is_empty() {
+        println!("没有同步项目可以修改。");
+        return Ok(());
+    }
+    
+    println!("同步项目列表:");
+    for (i, item) in config.sync_items.iter().enumerate() {
+        println!("  {}. {}", i + 1, item.name);
+    }
+    
+    print!("请输入要设置过滤规则的项目编号 (或输入0取消): ");
+    let mut input = String::new();
+    std::io::stdin().read_line(&mut input)?;
+    
+    let selection: usize = match input.trim().parse() {
+        Ok(num) if num > 0 && num <= config.sync_items.len() => num - 1,
+        Ok(0) => return Ok(()),
+        _ => {
+            println!("无效的选择。操作已取消。");
+            return Ok(());
+        }
+    };
+    
+    let item = &config.sync_items[selection];
+    println!("当前包含规则: {:?}", item.include);
+    println!("当前排除规则: {:?}", item.exclude);
+    println!("规则使用 gitignore 语法并以逗号分隔，例如: *.log, Cache/, !keep.log");
+    println!("直接回车保持不变，输入 - 清空。");
+    
+    let include = read_patterns("包含规则 (清空表示包含全部)")?;
+    
+    let exclude = match read_patterns("排除规则 (输入 default 恢复默认值)")? {
+        Some(patterns) if patterns == ["default"] => Some(default_exclude_patterns()),
+        other => other,
+    };
+    
+    let new_include = include.unwrap_or_else(|| item.include.clone());
+    let new_exclude = exclude.unwrap_or_else(|| item.exclude.clone());
+    
+    // 保存前先检查规则是否有效
+    PathFilter::new(&new_include, &new_exclude)?;
+    
+    let item = &mut config.sync_items[selection];
+    item.include = new_include;
+    item.exclude = new_exclude;
+    let item_name = item.name.clone();
+    
+    config.save()?;
+    
+    println!("已更新 {} 的过滤规则", item_name);
+    
+    Ok(())
+}
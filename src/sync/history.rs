@@ -0,0 +1,182 @@
+use anyhow::Result;
+use chrono::{Local, TimeZone};
+use console::style;
+use std::fs;
+use std::path::Path;
+
+use crate::config::{Config, SyncItem};
+use crate::sync::restore::backup_payload;
+use crate::sync::settings::{self, Format};
+use crate::sync::{index, list_backups_for_item, snapshot};
+
+// 要查询的键：INI 的节和键，或 JSON 路径
+enum Query {
+    Ini { section: String, key: String },
+    Json(String),
+}
+
+// 读取文件中被查询键的值，用于显示；文件或键不存在时返回说明
+fn lookup(path: &Path, query: &Query) -> String {
+    let text = match fs::read_to_string(path) {
+        Ok(text) => text,
+        Err(_) => return "(文件不存在)".to_string(),
+    };
+    
+    let value = match query {
+        Query::Ini { section, key } => Ok(settings::ini_value(&text, section, key)),
+        Query::Json(path) => settings::json_value(&text, path).map(|v| v.map(|v| match v {
+            serde_json::Value::String(s) => s,
+            other => other.to_string(),
+        })),
+    };
+    
+    match value {
+        Ok(Some(value)) => value,
+        Ok(None) => "(不存在)".to_string(),
+        Err(e) => format!("(无法解析: {:#})", e),
+    }
+}
+
+fn format_time(timestamp: Option<u64>) -> String {
+    timestamp
+        .and_then(|ts| Local.timestamp_opt(ts as i64, 0).single())
+        .map(|t| t.format("%Y-%m-%d %H:%M:%S").to_string())
+        .unwrap_or_else(|| "-".to_string())
+}
+
+// 在最新的快照中列出可以查询的设置文件
+fn settings_files(config: &Config, item: &SyncItem, latest: &Path) -> Result<Vec<String>> {
+    let content = snapshot::open_snapshot(config, latest)?;
+    let payload = backup_payload(content.path(), &item.source_path);
+    
+    Ok(index::scan_item_at(item, &payload)?
+        .into_iter()
+        .filter(|f| settings::format_of(&index::file_in(&payload, &f.path)) != Format::Text)
+        .map(|f| f.path)
+        .collect())
+}
+
+// 查询某个设置在项目所有快照中的值，按时间从旧到新列出并标出变化
+pub fn settings_history(config: &Config) -> Result<()> {
+    if config.sync_items.is_empty() {
+        println!("没有同步项目。");
+        return Ok(());
+    }
+    
+    println!("同步项目列表:");
+    for (i, item) in config.sync_items.iter().enumerate() {
+        println!("  {}. {}", i + 1, item.name);
+    }
+    
+    print!("请输入项目编号 (或输入0取消): ");
+    let mut input = String::new();
+    std::io::stdin().read_line(&mut input)?;
+    
+    let item = match input.trim().parse::<usize>() {
+        Ok(num) if num > 0 && num <= config.sync_items.len() => &config.sync_items[num - 1],
+        Ok(0) => return Ok(()),
+        _ => {
+            println!("无效的选择。操作已取消。");
+            return Ok(());
+        }
+    };
+    
+    // 最新的在前
+    let backups = list_backups_for_item(config, &item.name)?;
+    if backups.is_empty() {
+        println!("{} 没有任何快照。", item.name);
+        return Ok(());
+    }
+    
+    let files = settings_files(config, item, &backups[0])?;
+    if files.is_empty() {
+        println!("{} 的最新快照中没有 INI 或 JSON 文件。", item.name);
+        return Ok(());
+    }
+    
+    println!("设置文件:");
+    for (i, file) in files.iter().enumerate() {
+        let display = if file.is_empty() { "(项目文件)" } else { file.as_str() };
+        println!("  {}. {}", i + 1, display);
+    }
+    
+    print!("请选择文件 (或输入0取消): ");
+    let mut input = String::new();
+    std::io::stdin().read_line(&mut input)?;
+    
+    let file = match input.trim().parse::<usize>() {
+        Ok(num) if num > 0 && num <= files.len() => files[num - 1].clone(),
+        Ok(0) => return Ok(()),
+        _ => {
+            println!("无效的选择。操作已取消。");
+            return Ok(());
+        }
+    };
+    
+    let is_json = settings::format_of(&index::file_in(&item.source_path, &file)) == Format::Json;
+    if is_json {
+        print!("请输入 JSON 路径 (如 files[Game.cfg].sections[General].settings[MouseSpeed].value): ");
+    } else {
+        print!("请输入 \"节名/键名\" (如 General/MouseSpeed): ");
+    }
+    let mut input = String::new();
+    std::io::stdin().read_line(&mut input)?;
+    
+    let input = input.trim();
+    let query = if is_json {
+        Query::Json(input.to_string())
+    } else {
+        match input.split_once('/') {
+            Some((section, key)) if !key.trim().is_empty() => Query::Ini {
+                section: section.trim().to_string(),
+                key: key.trim().to_string(),
+            },
+            _ => {
+                println!("无效的键。操作已取消。");
+                return Ok(());
+            }
+        }
+    };
+    
+    println!("{} 在 {} 的快照中的值 (从旧到新):", input, item.name);
+    println!("  {:<20} {:<32} 值", "时间", "快照");
+    
+    let mut previous: Option<String> = None;
+    let mut changes = 0;
+    
+    let mut print_row = |time: String, label: String, value: String| {
+        let changed = previous.as_ref().is_some_and(|p| *p != value);
+        if changed {
+            changes += 1;
+            println!("  {:<20} {:<32} {}", time, label, style(format!("{}  <- 已变化", value)).yellow().bold());
+        } else {
+            println!("  {:<20} {:<32} {}", time, label, value);
+        }
+        previous = Some(value);
+    };
+    
+    for backup in backups.iter().rev() {
+        let name = backup
+            .file_name()
+            .map(|n| n.to_string_lossy().to_string())
+            .unwrap_or_default();
+        let timestamp = snapshot::parse_timestamp(&name, &item.name);
+        
+        let value = match snapshot::open_snapshot(config, backup) {
+            Ok(content) => {
+                let payload = backup_payload(content.path(), &item.source_path);
+                lookup(&index::file_in(&payload, &file), &query)
+            },
+            Err(e) => format!("(无法打开快照: {:#})", e),
+        };
+        
+        print_row(format_time(timestamp), name, value);
+    }
+    
+    let live = lookup(&index::file_in(&item.source_path, &file), &query);
+    print_row("-".to_string(), "当前源路径".to_string(), live);
+    
+    println!("共 {} 个快照，值变化了 {} 次。", backups.len(), changes);
+    
+    Ok(())
+}
\ No newline at end of file
@@ -0,0 +1,257 @@
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::fs;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::time::UNIX_EPOCH;
+
+use crate::config::{Config, SyncItem};
+use crate::sync::crypto;
+use crate::sync::filter::PathFilter;
+
+// 每个同步项目的变更检测索引存放在备份目录下的该子目录中
+const INDEX_DIR_NAME: &str = "snapshot_index";
+
+// 一个文件的路径、大小、修改时间和校验值；路径相对于项目的源目录，使用 '/' 分隔
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FileEntry {
+    pub path: String,
+    pub size: u64,
+    pub mtime: u64,
+    pub hash: String,
+}
+
+// 同步项目上一次备份时的文件状态
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct ItemIndex {
+    pub snapshot: Option<String>,
+    pub files: Vec<FileEntry>,
+}
+
+// 计算文件内容的 SHA-256
+pub fn hash_file(path: &Path) -> Result<String> {
+    let mut file = fs::File::open(path).with_context(|| format!("打开文件失败: {:?}", path))?;
+    let mut hasher = Sha256::new();
+    let mut buffer = [0u8; 64 * 1024];
+    
+    loop {
+        let read = file
+            .read(&mut buffer)
+            .with_context(|| format!("读取文件失败: {:?}", path))?;
+        if read == 0 {
+            break;
+        }
+        hasher.update(&buffer[..read]);
+    }
+    
+    Ok(hex::encode(hasher.finalize()))
+}
+
+fn modified_millis(meta: &fs::Metadata) -> u64 {
+    meta.modified()
+        .ok()
+        .and_then(|t| t.duration_since(UNIX_EPOCH).ok())
+        .map(|d| d.as_millis() as u64)
+        .unwrap_or(0)
+}
+
+// 扫描同步项目的源路径；大小和修改时间都没变的文件直接沿用上次的校验值
+pub fn scan_item(item: &SyncItem, previous: Option<&ItemIndex>) -> Result<Vec<FileEntry>> {
+    let known: HashMap<&str, &FileEntry> = previous
+        .map(|index| index.files.iter().map(|f| (f.path.as_str(), f)).collect())
+        .unwrap_or_default();
+    
+    let mut files = Vec::new();
+    
+    if item.source_path.is_dir() {
+        let filter = PathFilter::for_item(item)?;
+        scan_dir(&item.source_path, Path::new(""), &filter, &known, &mut files)?;
+    } else {
+        let name = item
+            .source_path
+            .file_name()
+            .map(|n| n.to_string_lossy().to_string())
+            .unwrap_or_default();
+        files.push(scan_file(&item.source_path, name, &known)?);
+    }
+    
+    files.sort_by(|a, b| a.path.cmp(&b.path));
+    
+    Ok(files)
+}
+
+fn scan_dir(
+    dir: &Path,
+    rel: &Path,
+    filter: &PathFilter,
+    known: &HashMap<&str, &FileEntry>,
+    files: &mut Vec<FileEntry>,
+) -> Result<()> {
+    let entries = fs::read_dir(dir).with_context(|| format!("读取目录失败: {:?}", dir))?;
+    
+    for entry in entries {
+        let entry = entry?;
+        let path = entry.path();
+        let child_rel = rel.join(entry.file_name());
+        let is_dir = path.is_dir();
+        
+        if !filter.is_included(&child_rel, is_dir) {
+            continue;
+        }
+        
+        if is_dir {
+            scan_dir(&path, &child_rel, filter, known, files)?;
+        } else {
+            files.push(scan_file(&path, rel_to_string(&child_rel), known)?);
+        }
+    }
+    
+    Ok(())
+}
+
+fn scan_file(path: &Path, rel: String, known: &HashMap<&str, &FileEntry>) -> Result<FileEntry> {
+    let meta = fs::metadata(path).with_context(|| format!("读取文件信息失败: {:?}", path))?;
+    let size = meta.len();
+    let mtime = modified_millis(&meta);
+    
+    let hash = match known.get(rel.as_str()) {
+        Some(entry) if entry.size == size && entry.mtime == mtime => entry.hash.clone(),
+        _ => hash_file(path)?,
+    };
+    
+    Ok(FileEntry {
+        path: rel,
+        size,
+        mtime,
+        hash,
+    })
+}
+
+// 按项目的过滤规则扫描任意位置（如快照内容）；root 是文件时其路径为空字符串，不存在时为空列表
+pub fn scan_item_at(item: &SyncItem, root: &Path) -> Result<Vec<FileEntry>> {
+    if root.is_dir() {
+        let item = SyncItem {
+            source_path: root.to_path_buf(),
+            ..item.clone()
+        };
+        scan_item(&item, None)
+    } else if root.exists() {
+        scan_tree(root)
+    } else {
+        Ok(Vec::new())
+    }
+}
+
+// 扫描任意文件或目录中的全部文件（不使用过滤规则）；root 是文件时其路径为空字符串
+pub fn scan_tree(root: &Path) -> Result<Vec<FileEntry>> {
+    let mut files = Vec::new();
+    let known = HashMap::new();
+    
+    if root.is_dir() {
+        scan_dir(root, Path::new(""), &PathFilter::new(&[], &[])?, &known, &mut files)?;
+    } else {
+        files.push(scan_file(root, String::new(), &known)?);
+    }
+    
+    files.sort_by(|a, b| a.path.cmp(&b.path));
+    
+    Ok(files)
+}
+
+// 只列出 root 中全部文件的相对路径，不计算校验值；root 是文件时其路径为空字符串
+pub fn list_tree(root: &Path) -> Result<Vec<String>> {
+    if !root.is_dir() {
+        return Ok(vec![String::new()]);
+    }
+    
+    let mut files = Vec::new();
+    list_dir(root, Path::new(""), &mut files)?;
+    files.sort();
+    
+    Ok(files)
+}
+
+fn list_dir(root: &Path, rel: &Path, files: &mut Vec<String>) -> Result<()> {
+    let dir = root.join(rel);
+    let entries = fs::read_dir(&dir).with_context(|| format!("读取目录失败: {:?}", dir))?;
+    
+    for entry in entries {
+        let entry = entry?;
+        let child_rel = rel.join(entry.file_name());
+        
+        if entry.path().is_dir() {
+            list_dir(root, &child_rel, files)?;
+        } else {
+            files.push(rel_to_string(&child_rel));
+        }
+    }
+    
+    Ok(())
+}
+
+// 文件在 root 中的实际路径；单文件项目的相对路径为空
+pub fn file_in(root: &Path, rel: &str) -> PathBuf {
+    if rel.is_empty() {
+        root.to_path_buf()
+    } else {
+        root.join(rel)
+    }
+}
+
+pub fn rel_to_string(rel: &Path) -> String {
+    rel.components()
+        .map(|c| c.as_os_str().to_string_lossy().to_string())
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+// 比较两组文件的路径、大小和校验值（忽略修改时间）
+pub fn same_content(a: &[FileEntry], b: &[FileEntry]) -> bool {
+    a.len() == b.len()
+        && a.iter()
+            .zip(b)
+            .all(|(x, y)| x.path == y.path && x.size == y.size && x.hash == y.hash)
+}
+
+fn index_path(config: &Config, item_name: &str) -> PathBuf {
+    config
+        .backup_dir
+        .join(INDEX_DIR_NAME)
+        .join(format!("{}.json", item_name))
+}
+
+pub fn load_index(config: &Config, item_name: &str) -> Result<Option<ItemIndex>> {
+    let path = index_path(config, item_name);
+    if !path.exists() {
+        return Ok(None);
+    }
+    
+    let index_str = crypto::read_to_string(config, &path)
+        .with_context(|| format!("读取变更索引失败: {:?}", path))?;
+    
+    // 索引损坏时当作没有索引，下一次备份会重新生成
+    match serde_json::from_str(&index_str) {
+        Ok(index) => Ok(Some(index)),
+        Err(e) => {
+            println!("警告: 变更索引 {:?} 无法解析，将重新生成: {}", path, e);
+            Ok(None)
+        }
+    }
+}
+
+pub fn save_index(config: &Config, item_name: &str, index: &ItemIndex) -> Result<()> {
+    let path = index_path(config, item_name);
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)
+            .with_context(|| format!("创建变更索引目录失败: {:?}", parent))?;
+    }
+    
+    let index_str = serde_json::to_string_pretty(index)
+        .with_context(|| "序列化变更索引失败")?;
+    crypto::write_file(config, &path, index_str.as_bytes())
+        .with_context(|| format!("写入变更索引失败: {:?}", path))?;
+    
+    Ok(())
+}
\ No newline at end of file
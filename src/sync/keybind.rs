@@ -0,0 +1,290 @@
+use anyhow::{Context, Result};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::config::{Config, SyncItem};
+use crate::sync::restore::backup_payload;
+use crate::sync::settings::{self, IniSection};
+use crate::sync::{index, snapshot};
+
+// 包含按键绑定的节；商店中的按键只在商店打开时生效，与其他节分开检查冲突
+const BINDING_SECTIONS: [&str; 4] = ["GameEvents", "HUDEvents", "ShopEvents", "ChatEvents"];
+const SHOP_SECTION: &str = "ShopEvents";
+
+// 客户端写入的"未绑定"值
+const UNBOUND: &str = "<Unbound>";
+
+// 重要的操作及其说明，未绑定时给出提示
+const IMPORTANT_ACTIONS: [(&str, &str); 21] = [
+    ("evtCastSpell1", "技能 Q"),
+    ("evtCastSpell2", "技能 W"),
+    ("evtCastSpell3", "技能 E"),
+    ("evtCastSpell4", "技能 R"),
+    ("evtCastAvatarSpell1", "召唤师技能 D"),
+    ("evtCastAvatarSpell2", "召唤师技能 F"),
+    ("evtUseItem1", "物品 1"),
+    ("evtUseItem2", "物品 2"),
+    ("evtUseItem3", "物品 3"),
+    ("evtUseItem4", "物品 4"),
+    ("evtUseItem5", "物品 5"),
+    ("evtUseItem6", "物品 6"),
+    ("evtUseItem7", "回城"),
+    ("evtUseVisionItem", "饰品"),
+    ("evtLevelSpell1", "升级技能 Q"),
+    ("evtLevelSpell2", "升级技能 W"),
+    ("evtLevelSpell3", "升级技能 E"),
+    ("evtLevelSpell4", "升级技能 R"),
+    ("evtPlayerAttackMove", "攻击移动"),
+    ("evtOpenShop", "打开商店"),
+    ("evtCameraLockToggle", "锁定视角"),
+];
+
+fn action_label(action: &str) -> Option<&'static str> {
+    IMPORTANT_ACTIONS
+        .iter()
+        .find(|(name, _)| *name == action)
+        .map(|(_, label)| *label)
+}
+
+// 一个操作的绑定
+pub struct Binding {
+    pub section: String,
+    pub action: String,
+    // 每个组合键，如 "Shift+Q"；空表示未绑定
+    pub keys: Vec<String>,
+}
+
+// 解析绑定值：多个组合键用逗号分隔，每个组合键由若干个 [键] 组成，如 "[Shift][q],[Button 4]"
+fn parse_keys(value: &str) -> Vec<String> {
+    let mut combos = Vec::new();
+    let mut current: Vec<String> = Vec::new();
+    let mut rest = value.trim();
+    
+    while !rest.is_empty() {
+        if let Some(after) = rest.strip_prefix('[') {
+            let end = after.find(']').unwrap_or(after.len());
+            let key = after[..end].trim();
+            if !key.is_empty() && key != UNBOUND {
+                current.push(display_key(key));
+            }
+            rest = after.get(end + 1..).unwrap_or("");
+        } else if let Some(after) = rest.strip_prefix(',') {
+            if !current.is_empty() {
+                combos.push(current.join("+"));
+                current.clear();
+            }
+            rest = after;
+        } else {
+            // 没有方括号的值按原样作为一个键
+            let end = rest.find([',', '[']).unwrap_or(rest.len());
+            let key = rest[..end].trim();
+            if !key.is_empty() {
+                current.push(display_key(key));
+            }
+            rest = &rest[end..];
+        }
+    }
+    
+    if !current.is_empty() {
+        combos.push(current.join("+"));
+    }
+    
+    combos
+}
+
+// 单个字母显示为大写，其余首字母大写，如 "q" -> "Q"、"shift" -> "Shift"
+fn display_key(key: &str) -> String {
+    let mut chars = key.chars();
+    match chars.next() {
+        Some(first) => first.to_uppercase().chain(chars).collect(),
+        None => String::new(),
+    }
+}
+
+pub fn parse_bindings(sections: &[IniSection]) -> Vec<Binding> {
+    sections
+        .iter()
+        .filter(|s| BINDING_SECTIONS.contains(&s.name.as_str()))
+        .flat_map(|section| {
+            section.entries.iter().map(move |(action, value)| Binding {
+                section: section.name.clone(),
+                action: action.clone(),
+                keys: parse_keys(value),
+            })
+        })
+        .collect()
+}
+
+// 同一个组合键绑定到多个操作的情况，按组合键列出操作
+pub fn find_conflicts(bindings: &[Binding]) -> Vec<(String, Vec<String>)> {
+    // 键为 (是否商店中的绑定, 小写的组合键)，值为 (显示用的组合键, 操作列表)
+    let mut by_key: BTreeMap<(bool, String), (String, Vec<String>)> = BTreeMap::new();
+    
+    for binding in bindings {
+        let is_shop = binding.section == SHOP_SECTION;
+        for key in &binding.keys {
+            let (_, actions) = by_key
+                .entry((is_shop, key.to_lowercase()))
+                .or_insert_with(|| (key.clone(), Vec::new()));
+            if !actions.contains(&binding.action) {
+                actions.push(binding.action.clone());
+            }
+        }
+    }
+    
+    by_key
+        .into_values()
+        .filter(|(_, actions)| actions.len() > 1)
+        .collect()
+}
+
+// 显式设为未绑定的重要操作；input.ini 只保存修改过的绑定，没有出现的操作使用游戏默认值
+pub fn unbound_important(bindings: &[Binding]) -> Vec<(&str, &'static str)> {
+    bindings
+        .iter()
+        .filter(|b| b.keys.is_empty())
+        .filter_map(|b| action_label(&b.action).map(|label| (b.action.as_str(), label)))
+        .collect()
+}
+
+fn print_report(path: &Path) -> Result<()> {
+    let text = fs::read_to_string(path).with_context(|| format!("读取文件失败: {:?}", path))?;
+    let bindings = parse_bindings(&settings::parse_ini(&text));
+    
+    println!("按键绑定 ({:?}):", path);
+    
+    if bindings.is_empty() {
+        println!("  文件中没有自定义的按键绑定，全部使用游戏默认值。");
+        return Ok(());
+    }
+    
+    let mut section = "";
+    for binding in &bindings {
+        if binding.section != section {
+            section = &binding.section;
+            println!("\n[{}]", section);
+        }
+        
+        let keys = if binding.keys.is_empty() { "(未绑定)".to_string() } else { binding.keys.join(", ") };
+        let label = action_label(&binding.action).unwrap_or("");
+        println!("  {:<32} {:<24} {}", binding.action, keys, label);
+    }
+    
+    let conflicts = find_conflicts(&bindings);
+    println!();
+    if conflicts.is_empty() {
+        println!("没有按键冲突。");
+    } else {
+        println!("按键冲突:");
+        for (key, actions) in &conflicts {
+            println!("  {} 同时绑定到: {}", key, actions.join(", "));
+        }
+    }
+    
+    let unbound = unbound_important(&bindings);
+    if !unbound.is_empty() {
+        println!("未绑定的重要操作:");
+        for (action, label) in unbound {
+            println!("  {} ({})", action, label);
+        }
+    }
+    
+    println!("注意: input.ini 只保存修改过的绑定，未列出的操作使用游戏默认按键。");
+    
+    Ok(())
+}
+
+// 找出目录或文件中的 input.ini
+fn find_input_files(item: &SyncItem, root: &Path) -> Result<Vec<PathBuf>> {
+    Ok(index::scan_item_at(item, root)?
+        .into_iter()
+        .map(|f| if f.path.is_empty() { root.to_path_buf() } else { root.join(&f.path) })
+        .filter(|p| {
+            p.file_name()
+                .map(|n| n.to_string_lossy().eq_ignore_ascii_case("input.ini"))
+                .unwrap_or(false)
+        })
+        .collect())
+}
+
+// 查看同步项目当前或某个快照中 input.ini 的按键绑定，并检查冲突
+pub fn inspect_keybindings(config: &Config) -> Result<()> {
+    if config.sync_items.is_empty() {
+        println!("没有同步项目。");
+        return Ok(());
+    }
+    
+    println!("同步项目列表:");
+    for (i, item) in config.sync_items.iter().enumerate() {
+        println!("  {}. {}", i + 1, item.name);
+    }
+    
+    print!("请输入项目编号 (或输入0取消): ");
+    let mut input = String::new();
+    std::io::stdin().read_line(&mut input)?;
+    
+    let item = match input.trim().parse::<usize>() {
+        Ok(num) if num > 0 && num <= config.sync_items.len() => &config.sync_items[num - 1],
+        Ok(0) => return Ok(()),
+        _ => {
+            println!("无效的选择。操作已取消。");
+            return Ok(());
+        }
+    };
+    
+    let snapshots = snapshot::filter_by_tag(config, snapshot::list_snapshots(config, &item.name)?)?;
+    println!("要查看的版本:");
+    println!("  0. 当前源路径");
+    for (i, snap) in snapshots.iter().enumerate() {
+        println!("  {}. {}{}", i + 1, snap.file_name(), snapshot::describe(config, &snap.path));
+    }
+    
+    print!("请选择: ");
+    let mut input = String::new();
+    std::io::stdin().read_line(&mut input)?;
+    
+    // 快照内容（可能是临时解压目录）在查看完成前必须保持存在
+    let (root, _content) = match input.trim().parse::<usize>() {
+        Ok(0) => (item.source_path.clone(), None),
+        Ok(num) if num <= snapshots.len() => {
+            let content = snapshot::open_snapshot(config, &snapshots[num - 1].path)?;
+            (backup_payload(content.path(), &item.source_path), Some(content))
+        },
+        _ => {
+            println!("无效的选择。操作已取消。");
+            return Ok(());
+        }
+    };
+    
+    let files = find_input_files(item, &root)?;
+    
+    let path = match files.len() {
+        0 => {
+            println!("{} 中没有 input.ini。", item.name);
+            return Ok(());
+        },
+        1 => files[0].clone(),
+        _ => {
+            println!("找到多个 input.ini:");
+            for (i, file) in files.iter().enumerate() {
+                println!("  {}. {:?}", i + 1, file);
+            }
+            
+            print!("请选择 (或输入0取消): ");
+            let mut input = String::new();
+            std::io::stdin().read_line(&mut input)?;
+            
+            match input.trim().parse::<usize>() {
+                Ok(num) if num > 0 && num <= files.len() => files[num - 1].clone(),
+                Ok(0) => return Ok(()),
+                _ => {
+                    println!("无效的选择。操作已取消。");
+                    return Ok(());
+                }
+            }
+        },
+    };
+    
+    print_report(&path)
+}
\ No newline at end of file
@@ -0,0 +1,229 @@
+use anyhow::{Context, Result};
+use std::fs::{self, Permissions};
+#[cfg(unix)]
+use std::os::unix::fs::PermissionsExt;
+use std::path::{Path, PathBuf};
+
+use crate::config::{Config, SyncItem};
+use crate::sync::filter::PathFilter;
+
+pub enum LockStatus {
+    Locked,
+    // 只有部分文件是只读的
+    Partial,
+    Unlocked,
+    Missing,
+}
+
+impl LockStatus {
+    pub fn label(&self) -> &'static str {
+        match self {
+            LockStatus::Locked => "已锁定",
+            LockStatus::Partial => "部分锁定",
+            LockStatus::Unlocked => "未锁定",
+            LockStatus::Missing => "源路径不存在",
+        }
+    }
+}
+
+// 修改权限中的只读属性；Unix 上解锁只加回所有者的写权限，不会让文件变成所有人可写
+pub fn apply_readonly(permissions: &mut Permissions, readonly: bool) {
+    #[cfg(unix)]
+    {
+        let mode = permissions.mode();
+        permissions.set_mode(if readonly { mode & !0o222 } else { mode | 0o200 });
+    }
+    
+    #[cfg(not(unix))]
+    permissions.set_readonly(readonly);
+}
+
+// 设置单个文件的只读属性
+pub fn set_file_readonly(path: &Path, readonly: bool) -> Result<()> {
+    let mut permissions = fs::metadata(path)
+        .with_context(|| format!("读取文件信息失败: {:?}", path))?
+        .permissions();
+    
+    if permissions.readonly() == readonly {
+        return Ok(());
+    }
+    
+    apply_readonly(&mut permissions, readonly);
+    fs::set_permissions(path, permissions)
+        .with_context(|| format!("修改文件权限失败: {:?}", path))?;
+    
+    Ok(())
+}
+
+// 对项目中参与同步的每个文件调用 f；被过滤规则排除的文件（日志等）不受影响
+fn for_each_file<F>(item: &SyncItem, mut f: F) -> Result<()>
+where
+    F: FnMut(&Path) -> Result<()>,
+{
+    if !item.source_path.is_dir() {
+        return f(&item.source_path);
+    }
+    
+    let filter = PathFilter::for_item(item)?;
+    visit(&item.source_path, Path::new(""), &filter, &mut f)
+}
+
+fn visit<F>(dir: &Path, rel: &Path, filter: &PathFilter, f: &mut F) -> Result<()>
+where
+    F: FnMut(&Path) -> Result<()>,
+{
+    for entry in fs::read_dir(dir).with_context(|| format!("读取目录失败: {:?}", dir))? {
+        let entry = entry?;
+        let path = entry.path();
+        let child_rel = rel.join(entry.file_name());
+        let is_dir = path.is_dir();
+        
+        if !filter.is_included(&child_rel, is_dir) {
+            continue;
+        }
+        
+        if is_dir {
+            visit(&path, &child_rel, filter, f)?;
+        } else {
+            f(&path)?;
+        }
+    }
+    
+    Ok(())
+}
+
+// 把项目的文件设为只读或可写
+pub fn set_locked(item: &SyncItem, locked: bool) -> Result<()> {
+    if !item.source_path.exists() {
+        return Ok(());
+    }
+    
+    for_each_file(item, |path| set_file_readonly(path, locked))
+}
+
+// 恢复前记录的锁定状态，恢复后按它重新锁定或解锁
+pub struct LockState {
+    status: LockStatus,
+    // 部分锁定时原来为只读的文件
+    readonly: Vec<PathBuf>,
+}
+
+impl LockState {
+    pub fn capture(item: &SyncItem) -> Result<Self> {
+        let status = lock_status(item);
+        let mut readonly = Vec::new();
+        
+        if let LockStatus::Partial = status {
+            for_each_file(item, |path| {
+                if fs::metadata(path).map(|m| m.permissions().readonly()).unwrap_or(false) {
+                    readonly.push(path.to_path_buf());
+                }
+                Ok(())
+            })?;
+        }
+        
+        Ok(LockState { status, readonly })
+    }
+    
+    pub fn restore(&self, item: &SyncItem) -> Result<()> {
+        match self.status {
+            LockStatus::Locked => set_locked(item, true),
+            LockStatus::Partial => {
+                set_locked(item, false)?;
+                for path in self.readonly.iter().filter(|path| path.exists()) {
+                    set_file_readonly(path, true)?;
+                }
+                Ok(())
+            },
+            LockStatus::Unlocked | LockStatus::Missing => set_locked(item, false),
+        }
+    }
+}
+
+pub fn lock_status(item: &SyncItem) -> LockStatus {
+    if !item.source_path.exists() {
+        return LockStatus::Missing;
+    }
+    
+    let mut locked = 0;
+    let mut total = 0;
+    let result = for_each_file(item, |path| {
+        total += 1;
+        if fs::metadata(path).map(|m| m.permissions().readonly()).unwrap_or(false) {
+            locked += 1;
+        }
+        Ok(())
+    });
+    
+    match result {
+        Ok(()) if total > 0 && locked == total => LockStatus::Locked,
+        Ok(()) if locked > 0 => LockStatus::Partial,
+        _ => LockStatus::Unlocked,
+    }
+}
+
+fn select_item(config: &Config, prompt: &str) -> Result<Option<usize>> {
+    if config.sync_items.is_empty() {
+        println!("没有同步项目。");
+        return Ok(None);
+    }
+    
+    println!("同步项目列表:");
+    for (i, item) in config.sync_items.iter().enumerate() {
+        let auto = if item.lock_after_restore { ", 恢复后自动锁定" } else { "" };
+        println!("  {}. {} [{}{}]", i + 1, item.name, lock_status(item).label(), auto);
+    }
+    
+    print!("{} (或输入0取消): ", prompt);
+    let mut input = String::new();
+    std::io::stdin().read_line(&mut input)?;
+    
+    match input.trim().parse::<usize>() {
+        Ok(num) if num > 0 && num <= config.sync_items.len() => Ok(Some(num - 1)),
+        Ok(0) => Ok(None),
+        _ => {
+            println!("无效的选择。操作已取消。");
+            Ok(None)
+        }
+    }
+}
+
+// 把项目的文件设为只读，防止客户端重置设置
+pub fn lock_sync_item(config: &Config) -> Result<()> {
+    if let Some(idx) = select_item(config, "请输入要锁定的项目编号")? {
+        let item = &config.sync_items[idx];
+        set_locked(item, true)?;
+        println!("已锁定 {}，客户端将无法修改这些文件。", item.name);
+    }
+    
+    Ok(())
+}
+
+pub fn unlock_sync_item(config: &Config) -> Result<()> {
+    if let Some(idx) = select_item(config, "请输入要解锁的项目编号")? {
+        let item = &config.sync_items[idx];
+        set_locked(item, false)?;
+        println!("已解锁 {}", item.name);
+    }
+    
+    Ok(())
+}
+
+// 切换项目的"恢复后自动锁定"选项
+pub fn toggle_lock_after_restore(config: &mut Config) -> Result<()> {
+    let idx = match select_item(config, "请输入要切换\"恢复后自动锁定\"的项目编号")? {
+        Some(idx) => idx,
+        None => return Ok(()),
+    };
+    
+    let item = &mut config.sync_items[idx];
+    item.lock_after_restore = !item.lock_after_restore;
+    let status = if item.lock_after_restore { "开启" } else { "关闭" };
+    let item_name = item.name.clone();
+    
+    config.save()?;
+    
+    println!("已{} {} 的恢复后自动锁定", status, item_name);
+    
+    Ok(())
+}
\ No newline at end of file
@@ -1,10 +1,40 @@
 use anyhow::{Context, Result};
-use fs_extra::dir::{copy, CopyOptions};
+use std::collections::HashSet;
 use std::fs;
 use std::path::{Path, PathBuf};
 use std::time::{SystemTime, UNIX_EPOCH};
 
-use crate::config::{Config, SyncItem, SyncGroup};  // 导入SyncGroup
+use crate::config::{default_exclude_patterns, Config, StorageMode, SyncItem, SyncGroup, ValidationMode};  // 导入SyncGroup
+
+pub mod archive;
+pub mod browse;
+pub mod crypto;
+pub mod diff;
+pub mod drift;
+pub mod filter;
+pub mod history;
+pub mod index;
+pub mod keybind;
+pub mod lock;
+pub mod partial;
+pub mod process;
+pub mod remap;
+pub mod restore;
+pub mod retention;
+pub mod schedule;
+pub mod search;
+pub mod sets;
+pub mod settings;
+pub mod snapshot;
+pub mod validate;
+pub mod verify;
+pub mod version;
+pub mod watch;
+
+use filter::PathFilter;
+use restore::RestoreSession;
+use sets::{SetMember, SnapshotSet};
+use snapshot::SnapshotLabel;
 
 // 添加同步项目
 pub fn add_sync_item(config: &mut Config) -> Result<()> {
@@ -53,6 +83,11 @@ pub fn add_sync_item(config: &mut Config) -> Result<()> {
         backup_path: backup_path.clone(),
         is_enabled: true,
         group: group.clone(), // 使用克隆而不是移动
+        retention: None,
+        include: Vec::new(),
+        exclude: default_exclude_patterns(),
+        lock_after_restore: false,
+        golden: None,
     };
     
     config.sync_items.push(sync_item);
@@ -91,9 +126,10 @@ pub fn list_sync_items(config: &Config) -> Result<()> {
         println!("无组项目:");
         for (i, item) in items.iter().enumerate() {
             let status = if item.is_enabled { "启用" } else { "禁用" };
-            println!("  {}. {} [{}]", i + 1, item.name, status);
+            println!("  {}. {} [{}] [{}]", i + 1, item.name, status, lock_label(item));
             println!("     源路径: {:?}", item.source_path);
             println!("     备份路径: {:?}", item.backup_path);
+            print_item_filters(item);
         }
     }
     
@@ -103,9 +139,10 @@ pub fn list_sync_items(config: &Config) -> Result<()> {
             println!("\n组: {}:", group_name);
             for (i, item) in items.iter().enumerate() {
                 let status = if item.is_enabled { "启用" } else { "禁用" };
-                println!("  {}. {} [{}]", i + 1, item.name, status);
+                println!("  {}. {} [{}] [{}]", i + 1, item.name, status, lock_label(item));
                 println!("     源路径: {:?}", item.source_path);
                 println!("     备份路径: {:?}", item.backup_path);
+                print_item_filters(item);
             }
         }
     }
@@ -113,6 +150,16 @@ pub fn list_sync_items(config: &Config) -> Result<()> {
     Ok(())
 }
 
+// 显示项目的过滤规则
+fn print_item_filters(item: &SyncItem) {
+    if !item.include.is_empty() {
+        println!("     包含: {}", item.include.join(", "));
+    }
+    if !item.exclude.is_empty() {
+        println!("     排除: {}", item.exclude.join(", "));
+    }
+}
+
 // 删除同步项目
 pub fn remove_sync_item(config: &mut Config) -> Result<()> {
     if config.sync_items.is_empty() {
@@ -183,8 +230,14 @@ pub fn toggle_sync_item(config: &mut Config) -> Result<()> {
     Ok(())
 }
 
-// 一键备份所有启用的同步项目
+// 一键备份所有启用的同步项目，可以为新快照添加备注和标签
 pub fn backup_all(config: &Config) -> Result<()> {
+    let label = snapshot::ask_label()?;
+    backup_enabled_items(config, &label)
+}
+
+// 备份所有启用的项目（不做交互，供游戏退出时的自动备份等使用）
+pub fn backup_enabled_items(config: &Config, label: &SnapshotLabel) -> Result<()> {
     config.ensure_backup_dir()?;
     
     let enabled_items: Vec<_> = config.sync_items
@@ -204,45 +257,159 @@ pub fn backup_all(config: &Config) -> Result<()> {
     
     println!("正在备份所有启用的同步项目...");
     
+    let mut created = 0;
+    let mut unchanged = 0;
+    let mut rejected = 0;
+    let mut members = Vec::new();
+    
     for item in enabled_items {
         if !Path::new(&item.source_path).exists() {
             println!("警告: 源路径不存在: {:?}", item.source_path);
             continue;
         }
         
-        // 创建带时间戳的备份目录
-        let backup_dir = config.backup_dir.join(format!("{}_{}", item.name, timestamp));
-        fs::create_dir_all(&backup_dir)
-            .with_context(|| format!("创建备份目录失败: {:?}", backup_dir))?;
-        
-        if Path::new(&item.source_path).is_dir() {
-            // 复制目录
-            let options = CopyOptions::new();
-            copy(&item.source_path, &backup_dir, &options).with_context(|| {
-                format!(
-                    "从 {:?} 备份到 {:?} 失败",
-                    item.source_path, backup_dir
-                )
-            })?;
-        } else {
-            // 复制文件
-            let file_name = item.source_path.file_name().unwrap_or_else(|| std::ffi::OsStr::new("backup"));
-            let target_path = backup_dir.join(file_name);
-            
-            fs::copy(&item.source_path, &target_path).with_context(|| {
-                format!(
-                    "从 {:?} 备份到 {:?} 失败",
-                    item.source_path, target_path
-                )
-            })?;
+        let snapshot = match backup_item(config, item, timestamp)? {
+            BackupOutcome::Created(snapshot) => {
+                created += 1;
+                if !label.is_empty() {
+                    snapshot::apply_label(config, &config.backup_dir.join(&snapshot), label)?;
+                }
+                snapshot
+            },
+            BackupOutcome::Unchanged(snapshot) => {
+                unchanged += 1;
+                snapshot
+            },
+            BackupOutcome::Rejected => {
+                rejected += 1;
+                continue;
+            },
+        };
+        members.push(SetMember { item: item.name.clone(), snapshot });
+    }
+    
+    println!("所有同步项目备份完成！新建 {} 个快照，{} 个项目未变化。", created, unchanged);
+    if rejected > 0 {
+        println!("{} 个项目未通过备份前校验，已跳过。", rejected);
+    }
+    if !label.is_empty() && unchanged > 0 {
+        println!("未变化的项目沿用旧快照，备注和标签只添加到新建的快照。");
+    }
+    
+    if !members.is_empty() {
+        let id = sets::save_set(config, None, timestamp, members)?;
+        println!("本次备份的快照已记录为快照集 {}", id);
+    }
+    
+    Ok(())
+}
+
+// 单个项目的备份结果
+pub enum BackupOutcome {
+    // 新建的快照名称
+    Created(String),
+    // 源路径自上次备份以来没有变化，沿用已有的快照
+    Unchanged(String),
+    // 设置文件未通过备份前校验，没有创建快照
+    Rejected,
+}
+
+// 把单个同步项目备份到带时间戳的目录；内容没有变化时跳过
+fn backup_item(config: &Config, item: &SyncItem, timestamp: u64) -> Result<BackupOutcome> {
+    config.ensure_backup_dir()?;
+    
+    let previous = index::load_index(config, &item.name)?;
+    let files = index::scan_item(item, previous.as_ref())?;
+    
+    // 上次的快照仍然存在且内容一致时不再重复备份
+    if let Some(previous) = &previous {
+        if let Some(last) = &previous.snapshot {
+            let last_path = config.backup_dir.join(last);
+            if last_path.exists() && index::same_content(&previous.files, &files) {
+                println!("未变化 {}: 沿用 {:?}", item.name, last_path);
+                return Ok(BackupOutcome::Unchanged(last.clone()));
+            }
+        }
+    }
+    
+    // 客户端写入中途崩溃会留下空的或截断的设置文件，不能让它们替代正常的快照
+    let problems = validate::check_item(item, &files, previous.as_ref());
+    if !problems.is_empty() {
+        println!("警告: {} 的设置文件未通过校验:", item.name);
+        for problem in &problems {
+            println!("  {}", problem);
         }
         
-        println!("已备份 {}: {:?} -> {:?}", item.name, item.source_path, backup_dir);
+        if config.backup_validation == ValidationMode::Skip {
+            println!("已跳过 {}，没有创建快照。", item.name);
+            return Ok(BackupOutcome::Rejected);
+        }
     }
     
-    println!("所有同步项目备份完成！");
+    let storage_mode = archive::storage_mode_for(config, item);
     
-    Ok(())
+    // 创建带时间戳的备份目录；压缩包模式下先在临时目录中准备内容
+    let snapshot_name = match storage_mode {
+        StorageMode::Directory => format!("{}_{}", item.name, timestamp),
+        StorageMode::Archive => format!("{}_{}.{}", item.name, timestamp, archive::extension(config.archive_format)),
+    };
+    let staged = crypto::StagedSnapshot::new(config, &config.backup_dir.join(&snapshot_name))?;
+    let scratch = match storage_mode {
+        StorageMode::Directory => None,
+        StorageMode::Archive => Some(snapshot::ScratchDir::new(&item.name)),
+    };
+    let backup_dir = match &scratch {
+        Some(scratch) => scratch.path().to_path_buf(),
+        None => staged.path(),
+    };
+    fs::create_dir_all(&backup_dir)
+        .with_context(|| format!("创建备份目录失败: {:?}", backup_dir))?;
+    
+    let file_name = item.source_path.file_name().unwrap_or_else(|| std::ffi::OsStr::new("backup"));
+    let target_path = backup_dir.join(file_name);
+    
+    if Path::new(&item.source_path).is_dir() {
+        // 按过滤规则复制目录
+        let filter = PathFilter::for_item(item)?;
+        filter::copy_filtered(&item.source_path, &target_path, &filter).with_context(|| {
+            format!(
+                "从 {:?} 备份到 {:?} 失败",
+                item.source_path, backup_dir
+            )
+        })?;
+    } else {
+        // 复制文件
+        fs::copy(&item.source_path, &target_path).with_context(|| {
+            format!(
+                "从 {:?} 备份到 {:?} 失败",
+                item.source_path, target_path
+            )
+        })?;
+    }
+    
+    // 按实际写入快照的内容生成校验清单
+    let manifest = index::scan_tree(&backup_dir)?;
+    
+    if scratch.is_some() {
+        archive::create_archive(&backup_dir, &staged.path(), config.archive_format)?;
+    }
+    let snapshot_path = staged.finish()?;
+    
+    snapshot::save_manifest(config, &snapshot_path, manifest)?;
+    version::record_version(config, &snapshot_path)?;
+    if !problems.is_empty() {
+        snapshot::mark_suspect(config, &snapshot_path, problems)?;
+        println!("快照已标记为可疑，恢复时需要强制确认。");
+    }
+    
+    index::save_index(config, &item.name, &index::ItemIndex {
+        snapshot: Some(snapshot_name.clone()),
+        files,
+    })?;
+    
+    println!("已备份 {}: {:?} -> {:?}", item.name, item.source_path, snapshot_path);
+    
+    Ok(BackupOutcome::Created(snapshot_name))
 }
 
 // 一键恢复所有启用的同步项目
@@ -267,131 +434,334 @@ pub fn restore_all(config: &Config) -> Result<()> {
         return Ok(());
     }
     
+    // 客户端在运行时恢复没有意义，它退出时会覆盖恢复的文件
+    if !process::ensure_game_closed(config)? {
+        println!("恢复操作已取消。");
+        return Ok(());
+    }
+    
     println!("正在恢复所有启用的同步项目...");
     
+    // 使用最近一次一键备份的快照集，避免混用不同时间的快照
+    let set = sets::latest_set(config, None)?;
+    let use_latest = match confirm_set_coverage(config, set.as_ref(), &enabled_items)? {
+        Some(use_latest) => use_latest,
+        None => {
+            println!("恢复操作已取消。");
+            return Ok(());
+        }
+    };
+    
+    let targets: Vec<(&str, &Path)> = enabled_items.iter().map(|item| (item.name.as_str(), item.source_path.as_path())).collect();
+    if !remap::confirm_targets(config, &targets)? {
+        println!("恢复操作已取消。");
+        return Ok(());
+    }
+    
+    let mut session = RestoreSession::begin(config);
+    
     for item in enabled_items {
-        // 查找最新的备份
-        let backups = list_backups_for_item(config, &item.name)?;
+        // 按路径映射表写入本机的对应位置
+        let item = &remap::remap_item(config, item);
+        let backup = match backup_for_item(config, item, set.as_ref(), &use_latest)? {
+            Some(backup) => backup,
+            None => {
+                println!("警告: 没有找到 {} 的备份", item.name);
+                continue;
+            }
+        };
         
-        if backups.is_empty() {
-            println!("警告: 没有找到 {} 的备份", item.name);
+        if !validate::confirm_restore(config, &backup)? || !version::confirm_version(config, &backup)? {
+            println!("已跳过 {}", item.name);
             continue;
         }
         
-        // 使用最新的备份（第一个）
-        let latest_backup = &backups[0];
+        restore_item(&mut session, item, &backup)?;
         
-        // 确保源路径的父目录存在
-        if let Some(parent) = item.source_path.parent() {
-            fs::create_dir_all(parent)
-                .with_context(|| format!("创建源路径的父目录失败: {:?}", parent))?;
+        println!("已恢复 {}: {:?} <- {:?}", item.name, item.source_path, backup);
+    }
+    
+    println!("所有同步项目恢复完成！");
+    println!("恢复前的内容已保存为安全快照，可在恢复选项中撤销上次恢复。");
+    
+    Ok(())
+}
+
+// 把单个同步项目恢复到指定的快照
+pub fn restore_item_from(config: &Config, item: &SyncItem, backup: &Path) -> Result<()> {
+    if !validate::confirm_restore(config, backup)? || !version::confirm_version(config, backup)? {
+        println!("恢复操作已取消。");
+        return Ok(());
+    }
+    
+    println!("要把 {} 恢复到快照 {:?} 吗？这将覆盖现有文件。", item.name, backup);
+    print!("请输入'yes'确认: ");
+    let mut input = String::new();
+    std::io::stdin().read_line(&mut input)?;
+    
+    if input.trim().to_lowercase() != "yes" {
+        println!("恢复操作已取消。");
+        return Ok(());
+    }
+    
+    // 客户端在运行时恢复没有意义，它退出时会覆盖恢复的文件
+    if !process::ensure_game_closed(config)? {
+        println!("恢复操作已取消。");
+        return Ok(());
+    }
+    
+    if !remap::confirm_targets(config, &[(item.name.as_str(), item.source_path.as_path())])? {
+        println!("恢复操作已取消。");
+        return Ok(());
+    }
+    
+    let item = &remap::remap_item(config, item);
+    let mut session = RestoreSession::begin(config);
+    restore_item(&mut session, item, backup)?;
+    
+    println!("已恢复 {}: {:?} <- {:?}", item.name, item.source_path, backup);
+    println!("恢复前的内容已保存为安全快照，可在恢复选项中撤销上次恢复。");
+    
+    Ok(())
+}
+
+// 把同步项目的某个快照恢复到任意路径，项目的源路径保持不变
+pub fn restore_to_path(config: &Config) -> Result<()> {
+    if config.sync_items.is_empty() {
+        println!("没有同步项目。");
+        return Ok(());
+    }
+    
+    println!("同步项目列表:");
+    for (i, item) in config.sync_items.iter().enumerate() {
+        println!("  {}. {}", i + 1, item.name);
+    }
+    
+    print!("请输入项目编号 (或输入0取消): ");
+    let mut input = String::new();
+    std::io::stdin().read_line(&mut input)?;
+    
+    let item = match input.trim().parse::<usize>() {
+        Ok(num) if num > 0 && num <= config.sync_items.len() => &config.sync_items[num - 1],
+        Ok(0) => return Ok(()),
+        _ => {
+            println!("无效的选择。操作已取消。");
+            return Ok(());
         }
-        
-        // 如果存在，则删除当前源路径
-        if Path::new(&item.source_path).exists() {
-            if Path::new(&item.source_path).is_dir() {
-                fs::remove_dir_all(&item.source_path).with_context(|| {
-                    format!("删除现有目录失败: {:?}", item.source_path)
-                })?;
-            } else {
-                fs::remove_file(&item.source_path).with_context(|| {
-                    format!("删除现有文件失败: {:?}", item.source_path)
-                })?;
-            }
+    };
+    
+    let snapshots = snapshot::filter_by_tag(config, snapshot::list_snapshots(config, &item.name)?)?;
+    if snapshots.is_empty() {
+        println!("{} 没有任何快照。", item.name);
+        return Ok(());
+    }
+    
+    println!("{} 的快照:", item.name);
+    for (i, snap) in snapshots.iter().enumerate() {
+        println!("  {}. {}{}", i + 1, snap.file_name(), snapshot::describe(config, &snap.path));
+    }
+    
+    print!("请选择快照 (或输入0取消): ");
+    let mut input = String::new();
+    std::io::stdin().read_line(&mut input)?;
+    
+    let snap = match input.trim().parse::<usize>() {
+        Ok(num) if num > 0 && num <= snapshots.len() => &snapshots[num - 1],
+        Ok(0) => return Ok(()),
+        _ => {
+            println!("无效的选择。操作已取消。");
+            return Ok(());
         }
+    };
+    
+    print!("请输入恢复到的路径 (原源路径: {:?}): ", item.source_path);
+    let mut input = String::new();
+    std::io::stdin().read_line(&mut input)?;
+    
+    if input.trim().is_empty() {
+        println!("无效的路径。操作已取消。");
+        return Ok(());
+    }
+    
+    if !validate::confirm_restore(config, &snap.path)? || !version::confirm_version(config, &snap.path)? {
+        println!("恢复操作已取消。");
+        return Ok(());
+    }
+    
+    // 恢复到其他位置时不做只读锁定
+    let mut target_item = item.clone();
+    target_item.source_path = PathBuf::from(input.trim());
+    target_item.lock_after_restore = false;
+    
+    println!("将把快照 {} 恢复到 {:?}，目标已有的内容会被覆盖。", snap.file_name(), target_item.source_path);
+    print!("请输入'yes'确认: ");
+    let mut input = String::new();
+    std::io::stdin().read_line(&mut input)?;
+    
+    if input.trim().to_lowercase() != "yes" {
+        println!("恢复操作已取消。");
+        return Ok(());
+    }
+    
+    let mut session = RestoreSession::begin(config);
+    restore_item(&mut session, &target_item, &snap.path)?;
+    
+    println!("已恢复 {}: {:?} <- {:?}", item.name, target_item.source_path, snap.path);
+    println!("恢复前的内容已保存为安全快照，可在恢复选项中撤销上次恢复。");
+    
+    Ok(())
+}
+
+// 恢复时使用的快照：优先使用快照集中的快照，不在快照集中或选择改用最新快照的项目使用它的最新快照
+fn backup_for_item(config: &Config, item: &SyncItem, set: Option<&SnapshotSet>, use_latest: &HashSet<String>) -> Result<Option<PathBuf>> {
+    if !use_latest.contains(&item.name) {
+        if let Some(member) = set.and_then(|set| set.member(&item.name)) {
+            return Ok(Some(config.backup_dir.join(&member.snapshot)));
+        }
+    }
+    
+    Ok(list_backups_for_item(config, &item.name)?.into_iter().next())
+}
+
+// 说明恢复使用的快照集；有项目不在快照集中，或在快照集之后有更新的单独快照（如监视模式、自动修复生成的快照）时
+// 列出它们并请用户确认。返回 None 表示取消，否则返回改用各自最新快照的项目
+fn confirm_set_coverage(config: &Config, set: Option<&SnapshotSet>, items: &[&SyncItem]) -> Result<Option<HashSet<String>>> {
+    let set = match set {
+        Some(set) => set,
+        None => {
+            println!("没有完整的快照集，每个项目使用各自最新的快照。");
+            return Ok(Some(HashSet::new()));
+        }
+    };
+    
+    println!("使用快照集 {}，其中的项目将恢复到同一次备份的状态。", set.id);
+    
+    let mut outside = Vec::new();
+    let mut newer = Vec::new();
+    for item in items {
+        let member = match set.member(&item.name) {
+            Some(member) => member,
+            None => {
+                outside.push(item.name.as_str());
+                continue;
+            }
+        };
         
-        // 从备份恢复
-        if Path::new(latest_backup).is_dir() {
-            // 如果备份是目录，我们需要找到里面的内容（通常是一个文件夹）
-            let entries = fs::read_dir(latest_backup)?;
-            let entry_paths: Vec<_> = entries.filter_map(Result::ok).map(|e| e.path()).collect();
-            
-            if entry_paths.len() == 1 && entry_paths[0].is_dir() {
-                // 如果只有一个子目录，复制其内容
-                let source_dir = &entry_paths[0];
-                let options = CopyOptions::new();
-                
-                if item.source_path.is_dir() {
-                    // 目标是目录，我们复制整个目录
-                    copy(source_dir, item.source_path.parent().unwrap(), &options).with_context(|| {
-                        format!(
-                            "从 {:?} 恢复到 {:?} 失败",
-                            source_dir, item.source_path
-                        )
-                    })?;
-                } else {
-                    // 目标是文件，我们需要找到备份中的对应文件
-                    let source_entries = fs::read_dir(source_dir)?;
-                    for entry in source_entries.filter_map(Result::ok) {
-                        if entry.path().is_file() {
-                            fs::copy(entry.path(), &item.source_path).with_context(|| {
-                                format!(
-                                    "从 {:?} 恢复到 {:?} 失败",
-                                    entry.path(), item.source_path
-                                )
-                            })?;
-                            break;
-                        }
-                    }
-                }
-            } else {
-                // 复制整个备份目录
-                let options = CopyOptions::new();
-                copy(latest_backup, item.source_path.parent().unwrap(), &options).with_context(|| {
-                    format!(
-                        "从 {:?} 恢复到 {:?} 失败",
-                        latest_backup, item.source_path
-                    )
-                })?;
+        let member_time = snapshot::parse_timestamp(&member.snapshot, &item.name).unwrap_or(0);
+        if let Some(latest) = snapshot::list_snapshots(config, &item.name)?.into_iter().next() {
+            if latest.timestamp > member_time {
+                newer.push((item.name.as_str(), member.snapshot.as_str(), latest.file_name()));
             }
-        } else {
-            // 备份是单个文件
-            fs::copy(latest_backup, &item.source_path).with_context(|| {
-                format!(
-                    "从 {:?} 恢复到 {:?} 失败",
-                    latest_backup, item.source_path
-                )
-            })?;
         }
+    }
+    
+    if outside.is_empty() && newer.is_empty() {
+        return Ok(Some(HashSet::new()));
+    }
+    
+    if !outside.is_empty() {
+        println!("以下项目不在快照集 {} 中（备份时被跳过或源路径不存在），将使用各自最新的快照，与其他项目不是同一次备份:", set.id);
+        for name in &outside {
+            println!("  {}", name);
+        }
+    }
+    
+    if newer.is_empty() {
+        print!("请输入'yes'继续恢复: ");
+        let mut input = String::new();
+        std::io::stdin().read_line(&mut input)?;
         
-        println!("已恢复 {}: {:?} <- {:?}", item.name, item.source_path, latest_backup);
+        return Ok(if input.trim().to_lowercase() == "yes" { Some(HashSet::new()) } else { None });
     }
     
-    println!("所有同步项目恢复完成！");
+    println!("以下项目在快照集 {} 之后有更新的快照，按快照集恢复会回到较旧的内容:", set.id);
+    for (name, member, latest) in &newer {
+        println!("  {}: 快照集中为 {}，最新为 {}", name, member, latest);
+    }
     
-    Ok(())
+    println!("1. 仍使用快照集中的快照");
+    println!("2. 这些项目改用各自最新的快照");
+    print!("请选择 (其他输入取消恢复): ");
+    let mut input = String::new();
+    std::io::stdin().read_line(&mut input)?;
+    
+    match input.trim() {
+        "1" => Ok(Some(HashSet::new())),
+        "2" => Ok(Some(newer.iter().map(|(name, _, _)| name.to_string()).collect())),
+        _ => Ok(None),
+    }
 }
 
-// 列出指定项目的所有备份，按照时间排序（最新的在前）
-fn list_backups_for_item(config: &Config, item_name: &str) -> Result<Vec<PathBuf>> {
-    if !config.backup_dir.exists() {
-        return Ok(Vec::new());
+// 只读锁定状态，开启了恢复后自动锁定的项目额外标注
+fn lock_label(item: &SyncItem) -> String {
+    let status = lock::lock_status(item).label();
+    if item.lock_after_restore {
+        format!("{}, 恢复后自动锁定", status)
+    } else {
+        status.to_string()
     }
+}
+
+// 从备份恢复单个同步项目，并按设置重新锁定或保持恢复前的锁定状态
+fn restore_item(session: &mut RestoreSession, item: &SyncItem, backup: &Path) -> Result<()> {
+    // Windows 上无法替换或删除只读文件，恢复前先解锁
+    let previous = lock::LockState::capture(item)?;
+    lock::set_locked(item, false)?;
     
-    let entries = fs::read_dir(&config.backup_dir)
-        .with_context(|| format!("读取备份目录失败: {:?}", config.backup_dir))?;
-    
-    let mut backups = Vec::new();
+    let result = restore_item_content(session, item, backup);
     
-    // 查找格式为 "item_name_timestamp" 的备份
-    for entry in entries {
-        let entry = entry?;
-        let path = entry.path();
-        let file_name = path.file_name().unwrap().to_string_lossy();
+    // 快照中的文件可能带有锁定时的只读属性；开启了恢复后自动锁定时锁定，否则放回恢复前的状态
+    let relock = if item.lock_after_restore && result.is_ok() {
+        lock::set_locked(item, true)
+    } else {
+        previous.restore(item)
+    };
+    result?;
+    relock
+}
+
+// 先拍安全快照，再暂存替换
+pub fn restore_item_content(session: &mut RestoreSession, item: &SyncItem, backup: &Path) -> Result<()> {
+    // 压缩包快照会先解压到临时目录，content 在恢复完成前必须保持存在
+    let content = snapshot::open_snapshot(session.config(), backup)?;
+    let payload = restore::backup_payload(content.path(), &item.source_path);
+    
+    if !payload.is_dir() {
+        session.restore(&payload, &item.source_path).with_context(|| {
+            format!(
+                "从 {:?} 恢复到 {:?} 失败",
+                payload, item.source_path
+            )
+        })?;
         
-        if file_name.starts_with(&format!("{}_", item_name)) {
-            backups.push(path);
-        }
+        return Ok(());
     }
     
-    // 按修改时间排序（最新的在前）
-    backups.sort_by(|a, b| {
-        let a_meta = fs::metadata(a).unwrap();
-        let b_meta = fs::metadata(b).unwrap();
-        b_meta.modified().unwrap().cmp(&a_meta.modified().unwrap())
-    });
+    // 目录按过滤规则恢复，并保留现有的被排除文件
+    let filter = PathFilter::for_item(item)?;
+    let source_path = &item.source_path;
+    
+    session.restore_with(source_path, |staging| {
+        filter::copy_filtered(&payload, staging, &filter)?;
+        if source_path.is_dir() {
+            filter::copy_excluded(source_path, staging, &filter)?;
+        }
+        Ok(())
+    }).with_context(|| {
+        format!(
+            "从 {:?} 恢复到 {:?} 失败",
+            payload, item.source_path
+        )
+    })?;
     
-    Ok(backups)
+    Ok(())
+}
+
+// 列出指定项目的所有备份，按照时间排序（最新的在前）
+fn list_backups_for_item(config: &Config, item_name: &str) -> Result<Vec<PathBuf>> {
+    let snapshots = snapshot::list_snapshots(config, item_name)?;
+    
+    Ok(snapshots.into_iter().map(|s| s.path).collect())
 }
 
 // 添加同步组
@@ -419,6 +789,9 @@ pub fn add_sync_group(config: &mut Config) -> Result<()> {
         name,
         description,
         is_enabled: true,
+        retention: None,
+        storage_mode: None,
+        schedule: None,
     });
     
     config.save()?;
@@ -447,6 +820,10 @@ pub fn list_sync_groups(config: &Config) -> Result<()> {
             .filter(|item| item.group.as_deref() == Some(&group.name))
             .count();
         println!("     包含 {} 个同步项目", items_count);
+        
+        if let Some(schedule) = &group.schedule {
+            println!("     备份计划: {}", schedule::describe(schedule));
+        }
     }
     
     Ok(())
@@ -594,6 +971,12 @@ pub fn backup_group(config: &Config) -> Result<()> {
         }
     }
     
+    let label = snapshot::ask_label()?;
+    backup_group_by_name(config, group_name, &label)
+}
+
+// 备份指定组中所有启用的项目（不做交互，供计划备份等自动任务使用）
+pub fn backup_group_by_name(config: &Config, group_name: &str, label: &SnapshotLabel) -> Result<()> {
     // 找到该组中的所有启用项目
     let group_items: Vec<_> = config.sync_items
         .iter()
@@ -614,43 +997,49 @@ pub fn backup_group(config: &Config) -> Result<()> {
     
     config.ensure_backup_dir()?;
     
+    let mut created = 0;
+    let mut unchanged = 0;
+    let mut rejected = 0;
+    let mut members = Vec::new();
+    
     for item in &group_items {
         if !Path::new(&item.source_path).exists() {
             println!("警告: 源路径不存在: {:?}", item.source_path);
             continue;
         }
         
-        // 创建带时间戳的备份目录
-        let backup_dir = config.backup_dir.join(format!("{}_{}", item.name, timestamp));
-        fs::create_dir_all(&backup_dir)
-            .with_context(|| format!("创建备份目录失败: {:?}", backup_dir))?;
-        
-        if Path::new(&item.source_path).is_dir() {
-            // 复制目录
-            let options = CopyOptions::new();
-            copy(&item.source_path, &backup_dir, &options).with_context(|| {
-                format!(
-                    "从 {:?} 备份到 {:?} 失败",
-                    item.source_path, backup_dir
-                )
-            })?;
-        } else {
-            // 复制文件
-            let file_name = item.source_path.file_name().unwrap_or_else(|| std::ffi::OsStr::new("backup"));
-            let target_path = backup_dir.join(file_name);
-            
-            fs::copy(&item.source_path, &target_path).with_context(|| {
-                format!(
-                    "从 {:?} 备份到 {:?} 失败",
-                    item.source_path, target_path
-                )
-            })?;
-        }
-        
-        println!("已备份 {}: {:?} -> {:?}", item.name, item.source_path, backup_dir);
+        let snapshot = match backup_item(config, item, timestamp)? {
+            BackupOutcome::Created(snapshot) => {
+                created += 1;
+                if !label.is_empty() {
+                    snapshot::apply_label(config, &config.backup_dir.join(&snapshot), label)?;
+                }
+                snapshot
+            },
+            BackupOutcome::Unchanged(snapshot) => {
+                unchanged += 1;
+                snapshot
+            },
+            BackupOutcome::Rejected => {
+                rejected += 1;
+                continue;
+            },
+        };
+        members.push(SetMember { item: item.name.clone(), snapshot });
+    }
+    
+    println!("组 '{}' 的备份完成！新建 {} 个快照，{} 个项目未变化。", group_name, created, unchanged);
+    if rejected > 0 {
+        println!("{} 个项目未通过备份前校验，已跳过。", rejected);
+    }
+    if !label.is_empty() && unchanged > 0 {
+        println!("未变化的项目沿用旧快照，备注和标签只添加到新建的快照。");
     }
     
-    println!("组 '{}' 的备份完成！", group_name);
+    if !members.is_empty() {
+        let id = sets::save_set(config, Some(group_name), timestamp, members)?;
+        println!("本次备份的快照已记录为快照集 {}", id);
+    }
     
     Ok(())
 }
@@ -705,97 +1094,55 @@ pub fn restore_group(config: &Config) -> Result<()> {
         return Ok(());
     }
     
+    // 客户端在运行时恢复没有意义，它退出时会覆盖恢复的文件
+    if !process::ensure_game_closed(config)? {
+        println!("恢复操作已取消。");
+        return Ok(());
+    }
+    
     println!("正在恢复组 '{}' 中的项目...", group_name);
     
+    // 使用该组最近一次备份的快照集，保证组内的项目来自同一次备份
+    let set = sets::latest_set(config, Some(group_name))?;
+    let use_latest = match confirm_set_coverage(config, set.as_ref(), &group_items)? {
+        Some(use_latest) => use_latest,
+        None => {
+            println!("恢复操作已取消。");
+            return Ok(());
+        }
+    };
+    
+    let targets: Vec<(&str, &Path)> = group_items.iter().map(|item| (item.name.as_str(), item.source_path.as_path())).collect();
+    if !remap::confirm_targets(config, &targets)? {
+        println!("恢复操作已取消。");
+        return Ok(());
+    }
+    
+    let mut session = RestoreSession::begin(config);
+    
     for item in group_items {
-        // 查找最新的备份
-        let backups = list_backups_for_item(config, &item.name)?;
+        // 按路径映射表写入本机的对应位置
+        let item = &remap::remap_item(config, item);
+        let backup = match backup_for_item(config, item, set.as_ref(), &use_latest)? {
+            Some(backup) => backup,
+            None => {
+                println!("警告: 没有找到 {} 的备份", item.name);
+                continue;
+            }
+        };
         
-        if backups.is_empty() {
-            println!("警告: 没有找到 {} 的备份", item.name);
+        if !validate::confirm_restore(config, &backup)? || !version::confirm_version(config, &backup)? {
+            println!("已跳过 {}", item.name);
             continue;
         }
         
-        // 使用最新的备份（第一个）
-        let latest_backup = &backups[0];
-        
-        // 确保源路径的父目录存在
-        if let Some(parent) = item.source_path.parent() {
-            fs::create_dir_all(parent)
-                .with_context(|| format!("创建源路径的父目录失败: {:?}", parent))?;
-        }
-        
-        // 如果存在，则删除当前源路径
-        if Path::new(&item.source_path).exists() {
-            if Path::new(&item.source_path).is_dir() {
-                fs::remove_dir_all(&item.source_path).with_context(|| {
-                    format!("删除现有目录失败: {:?}", item.source_path)
-                })?;
-            } else {
-                fs::remove_file(&item.source_path).with_context(|| {
-                    format!("删除现有文件失败: {:?}", item.source_path)
-                })?;
-            }
-        }
-        
-        // 从备份恢复，这部分代码与restore_all相同
-        if Path::new(latest_backup).is_dir() {
-            // 如果备份是目录，我们需要找到里面的内容（通常是一个文件夹）
-            let entries = fs::read_dir(latest_backup)?;
-            let entry_paths: Vec<_> = entries.filter_map(Result::ok).map(|e| e.path()).collect();
-            
-            if entry_paths.len() == 1 && entry_paths[0].is_dir() {
-                // 如果只有一个子目录，复制其内容
-                let source_dir = &entry_paths[0];
-                let options = CopyOptions::new();
-                
-                if item.source_path.is_dir() {
-                    // 目标是目录，我们复制整个目录
-                    copy(source_dir, item.source_path.parent().unwrap(), &options).with_context(|| {
-                        format!(
-                            "从 {:?} 恢复到 {:?} 失败",
-                            source_dir, item.source_path
-                        )
-                    })?;
-                } else {
-                    // 目标是文件，我们需要找到备份中的对应文件
-                    let source_entries = fs::read_dir(source_dir)?;
-                    for entry in source_entries.filter_map(Result::ok) {
-                        if entry.path().is_file() {
-                            fs::copy(entry.path(), &item.source_path).with_context(|| {
-                                format!(
-                                    "从 {:?} 恢复到 {:?} 失败",
-                                    entry.path(), item.source_path
-                                )
-                            })?;
-                            break;
-                        }
-                    }
-                }
-            } else {
-                // 复制整个备份目录
-                let options = CopyOptions::new();
-                copy(latest_backup, item.source_path.parent().unwrap(), &options).with_context(|| {
-                    format!(
-                        "从 {:?} 恢复到 {:?} 失败",
-                        latest_backup, item.source_path
-                    )
-                })?;
-            }
-        } else {
-            // 备份是单个文件
-            fs::copy(latest_backup, &item.source_path).with_context(|| {
-                format!(
-                    "从 {:?} 恢复到 {:?} 失败",
-                    latest_backup, item.source_path
-                )
-            })?;
-        }
+        restore_item(&mut session, item, &backup)?;
         
-        println!("已恢复 {}: {:?} <- {:?}", item.name, item.source_path, latest_backup);
+        println!("已恢复 {}: {:?} <- {:?}", item.name, item.source_path, backup);
     }
     
     println!("组 '{}' 的所有项目恢复完成！", group_name);
+    println!("恢复前的内容已保存为安全快照，可在恢复选项中撤销上次恢复。");
     
     Ok(())
 }
@@ -0,0 +1,436 @@
+use anyhow::{Context, Result};
+use std::fs;
+use std::path::Path;
+
+use crate::config::{Config, IniKey, RestoreSelection, SyncItem};
+use crate::sync::diff::{diff_keys, print_key_diff};
+use crate::sync::restore::{backup_payload, RestoreSession};
+use crate::sync::settings::{self, Format, IniChange, IniSection};
+use crate::sync::{index, lock, process, remap, snapshot, validate, version};
+
+fn read_text(path: &Path) -> Result<String> {
+    fs::read_to_string(path).with_context(|| format!("读取文件失败: {:?}", path))
+}
+
+fn find_section<'a>(sections: &'a [IniSection], name: &str) -> Option<&'a IniSection> {
+    sections.iter().find(|s| s.name == name)
+}
+
+// 根据选择计算要写入当前文件的改动：整节恢复时删除快照中没有的键，单独的键在快照中不存在时也删除
+fn selection_changes(snapshot: &[IniSection], live: &[IniSection], selection: &RestoreSelection) -> Vec<IniChange> {
+    let mut changes: Vec<IniChange> = Vec::new();
+    
+    for name in &selection.sections {
+        let snap_entries = find_section(snapshot, name).map(|s| s.entries.as_slice()).unwrap_or(&[]);
+        let live_entries = find_section(live, name).map(|s| s.entries.as_slice()).unwrap_or(&[]);
+        
+        for (key, value) in snap_entries {
+            changes.push(IniChange {
+                section: name.clone(),
+                key: key.clone(),
+                value: Some(value.clone()),
+            });
+        }
+        for (key, _) in live_entries {
+            if !snap_entries.iter().any(|(k, _)| k == key) {
+                changes.push(IniChange {
+                    section: name.clone(),
+                    key: key.clone(),
+                    value: None,
+                });
+            }
+        }
+    }
+    
+    for IniKey { section, key } in &selection.keys {
+        if changes.iter().any(|c| &c.section == section && &c.key == key) {
+            continue;
+        }
+        
+        let value = find_section(snapshot, section)
+            .and_then(|s| s.entries.iter().find(|(k, _)| k == key))
+            .map(|(_, v)| v.clone());
+        
+        changes.push(IniChange {
+            section: section.clone(),
+            key: key.clone(),
+            value,
+        });
+    }
+    
+    changes
+}
+
+fn describe_selection(selection: &RestoreSelection) -> String {
+    let mut parts: Vec<String> = selection.sections.iter().map(|s| format!("[{}]", s)).collect();
+    parts.extend(selection.keys.iter().map(|k| settings::ini_key(&k.section, &k.key)));
+    parts.extend(selection.paths.iter().cloned());
+    
+    let item = if selection.item.is_empty() { "(未记录项目)" } else { selection.item.as_str() };
+    let file = if selection.file.is_empty() { "(项目文件)" } else { selection.file.as_str() };
+    format!("{} / {}: {}", item, file, parts.join(", "))
+}
+
+// 从 INI 文件中挑选整节恢复的节和单独恢复的键
+fn choose_ini_parts(text: &str) -> Result<Option<(Vec<String>, Vec<IniKey>)>> {
+    let sections = settings::parse_ini(text);
+    
+    println!("快照中的节:");
+    for (i, section) in sections.iter().enumerate() {
+        let name = if section.name.is_empty() { "(无节名)" } else { section.name.as_str() };
+        println!("  {}. {} ({} 个键)", i + 1, name, section.entries.len());
+    }
+    
+    print!("请输入要整节恢复的节编号，多个用逗号分隔 (直接回车跳过): ");
+    let mut input = String::new();
+    std::io::stdin().read_line(&mut input)?;
+    
+    let mut selected_sections = Vec::new();
+    for part in input.split(',').map(str::trim).filter(|p| !p.is_empty()) {
+        match part.parse::<usize>() {
+            Ok(num) if num > 0 && num <= sections.len() => selected_sections.push(sections[num - 1].name.clone()),
+            _ => {
+                println!("无效的节编号: {}。操作已取消。", part);
+                return Ok(None);
+            }
+        }
+    }
+    
+    println!("还可以单独恢复某些键，格式为 \"节名/键名\"，例如 \"GameEvents/evtCastSpell1\"。");
+    print!("请输入要恢复的键，多个用逗号分隔 (直接回车跳过): ");
+    let mut input = String::new();
+    std::io::stdin().read_line(&mut input)?;
+    
+    let mut keys = Vec::new();
+    for part in input.split(',').map(str::trim).filter(|p| !p.is_empty()) {
+        match part.split_once('/') {
+            Some((section, key)) if !key.trim().is_empty() => keys.push(IniKey {
+                section: section.trim().to_string(),
+                key: key.trim().to_string(),
+            }),
+            _ => {
+                println!("无效的键: {}。操作已取消。", part);
+                return Ok(None);
+            }
+        }
+    }
+    
+    Ok(Some((selected_sections, keys)))
+}
+
+// 从 JSON 文件中挑选要恢复的路径：可以选择列出的设置块，也可以直接输入路径
+fn choose_json_paths(text: &str) -> Result<Option<Vec<String>>> {
+    let blocks = settings::json_blocks(&settings::parse_json(text, "快照中的文件")?);
+    
+    println!("快照中的设置块:");
+    for (i, block) in blocks.iter().enumerate() {
+        println!("  {}. {}", i + 1, block);
+    }
+    
+    print!("请输入要恢复的设置块编号，多个用逗号分隔 (直接回车跳过): ");
+    let mut input = String::new();
+    std::io::stdin().read_line(&mut input)?;
+    
+    let mut paths = Vec::new();
+    for part in input.split(',').map(str::trim).filter(|p| !p.is_empty()) {
+        match part.parse::<usize>() {
+            Ok(num) if num > 0 && num <= blocks.len() => paths.push(blocks[num - 1].clone()),
+            _ => {
+                println!("无效的设置块编号: {}。操作已取消。", part);
+                return Ok(None);
+            }
+        }
+    }
+    
+    println!("还可以直接输入路径，例如 \"files[Game.cfg].sections[HUD].settings[GlobalScale]\"。");
+    print!("请输入要恢复的路径，多个用分号分隔 (直接回车跳过): ");
+    let mut input = String::new();
+    std::io::stdin().read_line(&mut input)?;
+    
+    paths.extend(
+        input
+            .split(';')
+            .map(str::trim)
+            .filter(|p| !p.is_empty())
+            .map(str::to_string),
+    );
+    
+    Ok(Some(paths))
+}
+
+// 让用户从快照中的 INI 或 JSON 文件里挑选要恢复的内容，并可保存为命名选择
+fn new_selection(config: &mut Config, item_name: &str, files: &[String], payload: &Path) -> Result<Option<RestoreSelection>> {
+    println!("快照中的设置文件:");
+    for (i, file) in files.iter().enumerate() {
+        let display = if file.is_empty() { "(项目文件)" } else { file.as_str() };
+        println!("  {}. {}", i + 1, display);
+    }
+    
+    print!("请选择文件 (或输入0取消): ");
+    let mut input = String::new();
+    std::io::stdin().read_line(&mut input)?;
+    
+    let file = match input.trim().parse::<usize>() {
+        Ok(num) if num > 0 && num <= files.len() => files[num - 1].clone(),
+        Ok(0) => return Ok(None),
+        _ => {
+            println!("无效的选择。操作已取消。");
+            return Ok(None);
+        }
+    };
+    
+    let path = index::file_in(payload, &file);
+    let text = read_text(&path)?;
+    let mut selection = RestoreSelection {
+        name: String::new(),
+        item: item_name.to_string(),
+        file,
+        sections: Vec::new(),
+        keys: Vec::new(),
+        paths: Vec::new(),
+    };
+    
+    if settings::format_of(&path) == Format::Json {
+        match choose_json_paths(&text)? {
+            Some(paths) => selection.paths = paths,
+            None => return Ok(None),
+        }
+    } else {
+        match choose_ini_parts(&text)? {
+            Some((sections, keys)) => {
+                selection.sections = sections;
+                selection.keys = keys;
+            },
+            None => return Ok(None),
+        }
+    }
+    
+    if selection.sections.is_empty() && selection.keys.is_empty() && selection.paths.is_empty() {
+        println!("没有选择任何内容。操作已取消。");
+        return Ok(None);
+    }
+    
+    print!("输入名称以保存此选择供以后使用 (直接回车不保存): ");
+    let mut input = String::new();
+    std::io::stdin().read_line(&mut input)?;
+    
+    let name = input.trim().to_string();
+    if !name.is_empty() {
+        selection.name = name.clone();
+        config.restore_selections.retain(|s| s.name != name);
+        config.restore_selections.push(selection.clone());
+        config.save()?;
+        println!("已保存选择 '{}'", name);
+    }
+    
+    Ok(Some(selection))
+}
+
+// 部分恢复：把快照中 INI 文件的指定节或键、JSON 文件的指定路径合并到当前文件，文件的其余部分保持不变
+pub fn partial_restore(config: &mut Config) -> Result<()> {
+    if config.sync_items.is_empty() {
+        println!("没有同步项目。");
+        return Ok(());
+    }
+    
+    println!("同步项目列表:");
+    for (i, item) in config.sync_items.iter().enumerate() {
+        println!("  {}. {}", i + 1, item.name);
+    }
+    
+    print!("请输入项目编号 (或输入0取消): ");
+    let mut input = String::new();
+    std::io::stdin().read_line(&mut input)?;
+    
+    let item: SyncItem = match input.trim().parse::<usize>() {
+        Ok(num) if num > 0 && num <= config.sync_items.len() => config.sync_items[num - 1].clone(),
+        Ok(0) => return Ok(()),
+        _ => {
+            println!("无效的选择。操作已取消。");
+            return Ok(());
+        }
+    };
+    
+    let snapshots = snapshot::filter_by_tag(config, snapshot::list_snapshots(config, &item.name)?)?;
+    if snapshots.is_empty() {
+        println!("{} 没有任何快照。", item.name);
+        return Ok(());
+    }
+    
+    println!("{} 的快照:", item.name);
+    for (i, snap) in snapshots.iter().enumerate() {
+        println!("  {}. {}{}", i + 1, snap.file_name(), snapshot::describe(config, &snap.path));
+    }
+    
+    print!("请选择快照 (或输入0取消): ");
+    let mut input = String::new();
+    std::io::stdin().read_line(&mut input)?;
+    
+    let snap = match input.trim().parse::<usize>() {
+        Ok(num) if num > 0 && num <= snapshots.len() => &snapshots[num - 1],
+        Ok(0) => return Ok(()),
+        _ => {
+            println!("无效的选择。操作已取消。");
+            return Ok(());
+        }
+    };
+    
+    if !validate::confirm_restore(config, &snap.path)? || !version::confirm_version(config, &snap.path)? {
+        println!("恢复操作已取消。");
+        return Ok(());
+    }
+    
+    let content = snapshot::open_snapshot(config, &snap.path)?;
+    let payload = backup_payload(content.path(), &item.source_path);
+    
+    // 快照中可以按节或路径合并的文件
+    let files: Vec<String> = index::scan_item_at(&item, &payload)?
+        .into_iter()
+        .filter(|f| settings::format_of(&index::file_in(&payload, &f.path)) != Format::Text)
+        .map(|f| f.path)
+        .collect();
+    
+    if files.is_empty() {
+        println!("快照 {} 中没有 INI 或 JSON 文件。", snap.file_name());
+        return Ok(());
+    }
+    
+    // 只提供为该项目保存的选择，其他项目中的同名文件内容不同
+    let saved: Vec<RestoreSelection> = config
+        .restore_selections
+        .iter()
+        .filter(|s| s.item == item.name && files.contains(&s.file))
+        .cloned()
+        .collect();
+    
+    println!("要恢复的内容:");
+    println!("  0. 新建选择");
+    for (i, selection) in saved.iter().enumerate() {
+        println!("  {}. {} ({})", i + 1, selection.name, describe_selection(selection));
+    }
+    
+    print!("请选择: ");
+    let mut input = String::new();
+    std::io::stdin().read_line(&mut input)?;
+    
+    let selection = match input.trim().parse::<usize>() {
+        Ok(0) => match new_selection(config, &item.name, &files, &payload)? {
+            Some(selection) => selection,
+            None => return Ok(()),
+        },
+        Ok(num) if num <= saved.len() => saved[num - 1].clone(),
+        _ => {
+            println!("无效的选择。操作已取消。");
+            return Ok(());
+        }
+    };
+    
+    // 按路径映射表合并到本机的对应文件
+    let live_item = remap::remap_item(config, &item);
+    let target = index::file_in(&live_item.source_path, &selection.file);
+    let snapshot_path = index::file_in(&payload, &selection.file);
+    let snapshot_text = read_text(&snapshot_path)?;
+    let format = settings::format_of(&snapshot_path);
+    
+    let live_text = if target.exists() {
+        read_text(&target)?
+    } else if format == Format::Json {
+        "{}".to_string()
+    } else {
+        String::new()
+    };
+    
+    // JSON 任何一方无效时拒绝合并，避免写出损坏的文件
+    let merged = match format {
+        Format::Json => settings::merge_json(&live_text, &snapshot_text, &selection.paths)?,
+        _ => {
+            let changes = selection_changes(
+                &settings::parse_ini(&snapshot_text),
+                &settings::parse_ini(&live_text),
+                &selection,
+            );
+            settings::merge_ini(&live_text, &changes)
+        },
+    };
+    
+    let before = settings::flatten(format, &live_text)?.unwrap_or_default();
+    let after = settings::flatten(format, &merged)?.unwrap_or_default();
+    let diff = diff_keys(&before, &after);
+    
+    if diff.is_empty() {
+        println!("{:?} 中所选内容已与快照一致，无需恢复。", target);
+        return Ok(());
+    }
+    
+    println!("将对 {:?} 做以下修改，文件的其余部分保持不变:", target);
+    print_key_diff(&diff);
+    
+    print!("请输入'yes'确认: ");
+    let mut input = String::new();
+    std::io::stdin().read_line(&mut input)?;
+    
+    if input.trim().to_lowercase() != "yes" {
+        println!("恢复操作已取消。");
+        return Ok(());
+    }
+    
+    // 客户端在运行时恢复没有意义，它退出时会覆盖恢复的文件
+    if !process::ensure_game_closed(config)? {
+        println!("恢复操作已取消。");
+        return Ok(());
+    }
+    
+    // Windows 上无法替换或删除只读文件，恢复前先解锁
+    let previous = lock::LockState::capture(&live_item)?;
+    if target.exists() {
+        lock::set_file_readonly(&target, false)?;
+    }
+    
+    let mut session = RestoreSession::begin(config);
+    let result = session.restore_with(&target, |staging| {
+        fs::write(staging, &merged).with_context(|| format!("写入文件失败: {:?}", staging))
+    });
+    
+    // 开启了恢复后自动锁定且恢复成功时锁定，否则放回恢复前的锁定状态
+    let relock = if live_item.lock_after_restore && result.is_ok() {
+        lock::set_locked(&live_item, true)
+    } else {
+        previous.restore(&live_item)
+    };
+    result?;
+    relock?;
+    
+    println!("已将所选内容从 {} 恢复到 {:?}", snap.file_name(), target);
+    println!("恢复前的内容已保存为安全快照，可在恢复选项中撤销上次恢复。");
+    
+    Ok(())
+}
+
+// 删除已保存的部分恢复选择
+pub fn remove_restore_selection(config: &mut Config) -> Result<()> {
+    if config.restore_selections.is_empty() {
+        println!("没有已保存的选择。");
+        return Ok(());
+    }
+    
+    println!("已保存的选择:");
+    for (i, selection) in config.restore_selections.iter().enumerate() {
+        println!("  {}. {} ({})", i + 1, selection.name, describe_selection(selection));
+    }
+    
+    print!("请输入要删除的选择编号 (或输入0取消): ");
+    let mut input = String::new();
+    std::io::stdin().read_line(&mut input)?;
+    
+    match input.trim().parse::<usize>() {
+        Ok(num) if num > 0 && num <= config.restore_selections.len() => {
+            let removed = config.restore_selections.remove(num - 1);
+            config.save()?;
+            println!("已删除选择 '{}'", removed.name);
+        },
+        Ok(0) => {},
+        _ => println!("无效的选择。操作已取消。"),
+    }
+    
+    Ok(())
+}
\ No newline at end of file
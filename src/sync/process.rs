@@ -0,0 +1,511 @@
+use anyhow::{Context, Result};
+use std::process::Command;
+use std::sync::atomic::Ordering;
+use std::thread;
+use std::time::Duration;
+use sysinfo::{ProcessRefreshKind, ProcessesToUpdate, Signal, System};
+
+use crate::config::Config;
+use crate::sync::watch::{log, stop_on_enter};
+use crate::sync::snapshot::SnapshotLabel;
+use crate::sync::{backup_enabled_items, backup_group_by_name};
+
+// 检查游戏进程的间隔
+const POLL_INTERVAL: Duration = Duration::from_secs(2);
+// 游戏退出后等待客户端把设置写完再备份
+const EXIT_SETTLE: Duration = Duration::from_secs(5);
+// 恢复前等待游戏退出的最长时间
+const WAIT_TIMEOUT: Duration = Duration::from_secs(120);
+// 请求关闭进程后等待其退出的时间
+const TERMINATE_TIMEOUT: Duration = Duration::from_secs(15);
+
+// 查询进程是否在运行；抽象成 trait 以便用假实现代替真实的系统进程列表
+pub trait ProcessProbe {
+    // 返回 names 中当前正在运行的进程名（不区分大小写）
+    fn running(&mut self, names: &[String]) -> Vec<String>;
+    
+    // 请求关闭指定名称的进程；force 为 false 时让进程正常退出
+    fn terminate(&mut self, name: &str, force: bool) -> Result<()>;
+    
+    // 两次查询之间的等待；假实现不需要真的等待
+    fn pause(&mut self, duration: Duration) {
+        thread::sleep(duration);
+    }
+}
+
+// 通过操作系统的进程列表查询
+#[derive(Default)]
+pub struct SystemProbe {
+    system: System,
+}
+
+impl ProcessProbe for SystemProbe {
+    fn running(&mut self, names: &[String]) -> Vec<String> {
+        self.system
+            .refresh_processes_specifics(ProcessesToUpdate::All, true, ProcessRefreshKind::nothing());
+        
+        names
+            .iter()
+            .filter(|name| {
+                self.system
+                    .processes()
+                    .values()
+                    .any(|p| p.name().to_string_lossy().eq_ignore_ascii_case(name))
+            })
+            .cloned()
+            .collect()
+    }
+    
+    fn terminate(&mut self, name: &str, force: bool) -> Result<()> {
+        // Windows 上正常关闭需要向窗口发送关闭消息，由不带 /F 的 taskkill 完成
+        if cfg!(windows) && !force {
+            let status = Command::new("taskkill")
+                .args(["/IM", name])
+                .status()
+                .with_context(|| "运行 taskkill 失败")?;
+            if !status.success() {
+                return Err(anyhow::anyhow!("taskkill 无法关闭 {}", name));
+            }
+            return Ok(());
+        }
+        
+        self.system
+            .refresh_processes_specifics(ProcessesToUpdate::All, true, ProcessRefreshKind::nothing());
+        
+        let signal = if force { Signal::Kill } else { Signal::Term };
+        for process in self.system.processes().values() {
+            if process.name().to_string_lossy().eq_ignore_ascii_case(name) {
+                process.kill_with(signal);
+            }
+        }
+        
+        Ok(())
+    }
+}
+
+// 假的进程列表，测试时直接设置哪些进程"正在运行"
+#[cfg(test)]
+#[derive(Default)]
+pub struct FakeProbe {
+    pub running: Vec<String>,
+    // 再查询多少次之后所有进程自行退出；为空时一直运行
+    pub exit_after: Option<usize>,
+    // 为 true 时进程不响应正常关闭，只能强制结束
+    pub ignore_graceful: bool,
+    // 收到的关闭请求：(进程名, 是否强制)
+    pub terminated: Vec<(String, bool)>,
+}
+
+#[cfg(test)]
+impl ProcessProbe for FakeProbe {
+    fn running(&mut self, names: &[String]) -> Vec<String> {
+        match self.exit_after {
+            Some(0) => self.running.clear(),
+            Some(n) => self.exit_after = Some(n - 1),
+            None => {},
+        }
+        
+        names
+            .iter()
+            .filter(|name| self.running.iter().any(|r| r.eq_ignore_ascii_case(name)))
+            .cloned()
+            .collect()
+    }
+    
+    fn terminate(&mut self, name: &str, force: bool) -> Result<()> {
+        self.terminated.push((name.to_string(), force));
+        if force || !self.ignore_graceful {
+            self.running.retain(|r| !r.eq_ignore_ascii_case(name));
+        }
+        Ok(())
+    }
+    
+    fn pause(&mut self, _duration: Duration) {}
+}
+
+pub enum GameEvent {
+    NoChange,
+    Started(Vec<String>),
+    // 所有被监视的进程都已退出
+    Exited,
+}
+
+// 跟踪游戏和客户端进程的启动与退出
+pub struct GameMonitor {
+    probe: Box<dyn ProcessProbe>,
+    names: Vec<String>,
+    running: Vec<String>,
+}
+
+impl GameMonitor {
+    pub fn new(probe: Box<dyn ProcessProbe>, names: Vec<String>) -> Self {
+        GameMonitor {
+            probe,
+            names,
+            running: Vec::new(),
+        }
+    }
+    
+    pub fn for_config(config: &Config) -> Self {
+        Self::new(Box::new(SystemProbe::default()), config.process_monitor.process_names.clone())
+    }
+    
+    pub fn poll(&mut self) -> GameEvent {
+        let now = self.probe.running(&self.names);
+        let was_running = !self.running.is_empty();
+        let is_running = !now.is_empty();
+        self.running = now;
+        
+        match (was_running, is_running) {
+            (false, true) => GameEvent::Started(self.running.clone()),
+            (true, false) => GameEvent::Exited,
+            _ => GameEvent::NoChange,
+        }
+    }
+    
+    pub fn is_running(&self) -> bool {
+        !self.running.is_empty()
+    }
+}
+
+// 游戏退出后按设置备份指定组或所有启用的项目
+fn backup_after_exit(config: &Config) {
+    let result = match &config.process_monitor.backup_group {
+        Some(group) => backup_group_by_name(config, group, &SnapshotLabel::default()),
+        None => backup_enabled_items(config, &SnapshotLabel::default()),
+    };
+    
+    if let Err(e) = result {
+        log(&format!("游戏退出后的自动备份失败: {:#}", e));
+    }
+}
+
+fn run_monitor(config: &Config, monitor: &mut GameMonitor) -> Result<()> {
+    let stop = stop_on_enter();
+    
+    while !stop.load(Ordering::SeqCst) {
+        match monitor.poll() {
+            GameEvent::Started(names) => log(&format!("检测到游戏运行: {}", names.join(", "))),
+            GameEvent::Exited => {
+                log(&format!("游戏已退出，{} 秒后开始备份", EXIT_SETTLE.as_secs()));
+                thread::sleep(EXIT_SETTLE);
+                backup_after_exit(config);
+            },
+            GameEvent::NoChange => {},
+        }
+        
+        thread::sleep(POLL_INTERVAL);
+    }
+    
+    log("游戏进程监视已停止");
+    
+    Ok(())
+}
+
+// 监视游戏和客户端进程，全部退出后自动备份；按回车键停止
+pub fn backup_on_game_exit(config: &Config) -> Result<()> {
+    if config.process_monitor.process_names.is_empty() {
+        println!("没有配置要监视的游戏进程。");
+        return Ok(());
+    }
+    
+    let target = match &config.process_monitor.backup_group {
+        Some(group) => format!("组 '{}'", group),
+        None => "所有启用的项目".to_string(),
+    };
+    
+    println!(
+        "正在监视进程: {}。游戏退出后将备份{}，按回车键停止。",
+        config.process_monitor.process_names.join(", "),
+        target
+    );
+    
+    let mut monitor = GameMonitor::for_config(config);
+    run_monitor(config, &mut monitor)
+}
+
+// 等待所有进程退出，超时返回 false
+fn wait_for_exit(probe: &mut dyn ProcessProbe, names: &[String], timeout: Duration) -> bool {
+    let mut waited = Duration::ZERO;
+    
+    loop {
+        if probe.running(names).is_empty() {
+            return true;
+        }
+        if waited >= timeout {
+            return false;
+        }
+        probe.pause(POLL_INTERVAL);
+        waited += POLL_INTERVAL;
+    }
+}
+
+fn terminate_all(probe: &mut dyn ProcessProbe, running: &[String], force: bool) {
+    for name in running {
+        if let Err(e) = probe.terminate(name, force) {
+            println!("关闭 {} 失败: {:#}", name, e);
+        }
+    }
+}
+
+// 游戏或客户端仍在运行时用户的选择
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum CloseChoice {
+    Wait,
+    Terminate,
+    Cancel,
+}
+
+// 恢复前确认游戏和客户端都没有运行，否则由 choose 选择等待、关闭或取消，
+// 正常关闭没有响应时由 confirm_force 决定是否强制结束；返回 false 表示取消恢复
+pub fn ensure_closed(
+    probe: &mut dyn ProcessProbe,
+    names: &[String],
+    choose: &mut dyn FnMut() -> Result<CloseChoice>,
+    confirm_force: &mut dyn FnMut() -> Result<bool>,
+) -> Result<bool> {
+    loop {
+        let running = probe.running(names);
+        if running.is_empty() {
+            return Ok(true);
+        }
+        
+        println!("检测到正在运行: {}", running.join(", "));
+        println!("客户端退出时会覆盖恢复的文件，请先关闭游戏和客户端。");
+        
+        match choose()? {
+            CloseChoice::Wait => {
+                println!("正在等待进程退出...");
+                if !wait_for_exit(probe, names, WAIT_TIMEOUT) {
+                    println!("等待超时，进程仍在运行。");
+                }
+            },
+            CloseChoice::Terminate => {
+                terminate_all(probe, &running, false);
+                println!("已请求关闭，正在等待进程退出...");
+                
+                if wait_for_exit(probe, names, TERMINATE_TIMEOUT) {
+                    continue;
+                }
+                
+                if confirm_force()? {
+                    let running = probe.running(names);
+                    terminate_all(probe, &running, true);
+                    wait_for_exit(probe, names, TERMINATE_TIMEOUT);
+                }
+            },
+            CloseChoice::Cancel => return Ok(false),
+        }
+    }
+}
+
+fn ask_close_choice() -> Result<CloseChoice> {
+    println!("  1. 等待其退出 (最多 {} 秒)", WAIT_TIMEOUT.as_secs());
+    println!("  2. 尝试正常关闭");
+    println!("  3. 取消恢复");
+    
+    print!("请选择: ");
+    let mut input = String::new();
+    std::io::stdin().read_line(&mut input)?;
+    
+    Ok(match input.trim() {
+        "1" => CloseChoice::Wait,
+        "2" => CloseChoice::Terminate,
+        _ => CloseChoice::Cancel,
+    })
+}
+
+fn ask_force() -> Result<bool> {
+    print!("进程没有响应。请输入'yes'强制结束 (未保存的内容会丢失): ");
+    let mut input = String::new();
+    std::io::stdin().read_line(&mut input)?;
+    
+    Ok(input.trim().to_lowercase() == "yes")
+}
+
+// 使用系统进程列表和配置中的进程名检查，在终端中询问用户
+pub fn ensure_game_closed(config: &Config) -> Result<bool> {
+    ensure_closed(
+        &mut SystemProbe::default(),
+        &config.process_monitor.process_names,
+        &mut ask_close_choice,
+        &mut ask_force,
+    )
+}
+
+// 设置要监视的进程名和游戏退出后备份的组
+pub fn set_process_monitor(config: &mut Config) -> Result<()> {
+    println!("当前监视的进程: {}", config.process_monitor.process_names.join(", "));
+    
+    print!("请输入进程名，多个用逗号分隔 (直接回车保持不变): ");
+    let mut input = String::new();
+    std::io::stdin().read_line(&mut input)?;
+    
+    let names: Vec<String> = input
+        .split(',')
+        .map(|name| name.trim().to_string())
+        .filter(|name| !name.is_empty())
+        .collect();
+    
+    if !names.is_empty() {
+        config.process_monitor.process_names = names;
+    }
+    
+    println!("游戏退出后要备份的内容:");
+    println!("  0. 所有启用的项目");
+    for (i, group) in config.sync_groups.iter().enumerate() {
+        println!("  {}. 组: {}", i + 1, group.name);
+    }
+    
+    print!("请选择 (直接回车保持不变): ");
+    let mut input = String::new();
+    std::io::stdin().read_line(&mut input)?;
+    
+    match input.trim() {
+        "" => {},
+        "0" => config.process_monitor.backup_group = None,
+        other => match other.parse::<usize>() {
+            Ok(num) if num > 0 && num <= config.sync_groups.len() => {
+                config.process_monitor.backup_group = Some(config.sync_groups[num - 1].name.clone());
+            },
+            _ => {
+                println!("无效的选择。操作已取消。");
+                return Ok(());
+            }
+        },
+    }
+    
+    config.save()?;
+    println!("游戏进程监视设置已更新。");
+    
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    fn names() -> Vec<String> {
+        vec!["League of Legends.exe".to_string(), "LeagueClient.exe".to_string()]
+    }
+    
+    #[test]
+    fn poll_reports_start_and_exit_once() {
+        let probe = FakeProbe {
+            running: vec!["leagueclient.exe".to_string()],
+            exit_after: Some(2),
+            ..FakeProbe::default()
+        };
+        let mut monitor = GameMonitor::new(Box::new(probe), names());
+        
+        match monitor.poll() {
+            GameEvent::Started(running) => assert_eq!(running, vec!["LeagueClient.exe".to_string()]),
+            _ => panic!("应当报告游戏启动"),
+        }
+        assert!(monitor.is_running());
+        assert!(matches!(monitor.poll(), GameEvent::NoChange));
+        assert!(matches!(monitor.poll(), GameEvent::Exited));
+        assert!(!monitor.is_running());
+        assert!(matches!(monitor.poll(), GameEvent::NoChange));
+    }
+    
+    #[test]
+    fn poll_ignores_unwatched_processes() {
+        let probe = FakeProbe {
+            running: vec!["notepad.exe".to_string()],
+            ..FakeProbe::default()
+        };
+        let mut monitor = GameMonitor::new(Box::new(probe), names());
+        
+        assert!(matches!(monitor.poll(), GameEvent::NoChange));
+        assert!(!monitor.is_running());
+    }
+    
+    #[test]
+    fn wait_succeeds_when_process_exits() {
+        let mut probe = FakeProbe {
+            running: vec!["LeagueClient.exe".to_string()],
+            exit_after: Some(3),
+            ..FakeProbe::default()
+        };
+        let mut choices = 0;
+        
+        let closed = ensure_closed(
+            &mut probe,
+            &names(),
+            &mut || {
+                choices += 1;
+                Ok(CloseChoice::Wait)
+            },
+            &mut || panic!("等待时不应询问强制结束"),
+        )
+        .unwrap();
+        
+        assert!(closed);
+        assert_eq!(choices, 1);
+        assert!(probe.terminated.is_empty());
+    }
+    
+    #[test]
+    fn graceful_terminate_closes_processes() {
+        let mut probe = FakeProbe {
+            running: vec!["LeagueClient.exe".to_string(), "League of Legends.exe".to_string()],
+            ..FakeProbe::default()
+        };
+        
+        let closed = ensure_closed(
+            &mut probe,
+            &names(),
+            &mut || Ok(CloseChoice::Terminate),
+            &mut || panic!("正常关闭成功时不应询问强制结束"),
+        )
+        .unwrap();
+        
+        assert!(closed);
+        assert!(probe.terminated.iter().all(|(_, force)| !force));
+        assert_eq!(probe.terminated.len(), 2);
+    }
+    
+    #[test]
+    fn force_follows_unanswered_terminate() {
+        let mut probe = FakeProbe {
+            running: vec!["LeagueClient.exe".to_string()],
+            ignore_graceful: true,
+            ..FakeProbe::default()
+        };
+        let mut forced = 0;
+        
+        let closed = ensure_closed(
+            &mut probe,
+            &names(),
+            &mut || Ok(CloseChoice::Terminate),
+            &mut || {
+                forced += 1;
+                Ok(true)
+            },
+        )
+        .unwrap();
+        
+        assert!(closed);
+        assert_eq!(forced, 1);
+        assert_eq!(
+            probe.terminated,
+            vec![("LeagueClient.exe".to_string(), false), ("LeagueClient.exe".to_string(), true)]
+        );
+    }
+    
+    #[test]
+    fn cancel_keeps_processes_running() {
+        let mut probe = FakeProbe {
+            running: vec!["LeagueClient.exe".to_string()],
+            ..FakeProbe::default()
+        };
+        
+        let closed = ensure_closed(&mut probe, &names(), &mut || Ok(CloseChoice::Cancel), &mut || Ok(true)).unwrap();
+        
+        assert!(!closed);
+        assert!(probe.terminated.is_empty());
+        assert_eq!(probe.running.len(), 1);
+    }
+}
+
@@ -0,0 +1,152 @@
+use anyhow::Result;
+use std::path::{Path, PathBuf};
+
+use crate::config::{Config, PathRemap, SyncItem};
+
+// 统一分隔符并把 ASCII 字母转为小写，Windows 路径不区分大小写；字节长度保持不变
+fn normalize(path: &str) -> String {
+    path.replace('\\', "/").to_ascii_lowercase()
+}
+
+// 按路径映射表替换路径前缀，多条规则匹配时使用最长的前缀；没有匹配时原样返回
+pub fn remap_path(config: &Config, path: &Path) -> PathBuf {
+    let original = path.to_string_lossy();
+    let normalized = normalize(&original);
+    
+    let mut best: Option<(usize, &PathRemap, &str)> = None;
+    for remap in &config.path_remaps {
+        let from = normalize(&remap.from.to_string_lossy());
+        let from = from.trim_end_matches('/');
+        if from.is_empty() {
+            continue;
+        }
+        
+        // 前缀必须在路径分隔处结束，"D:/Games" 不匹配 "D:/Games2"
+        let rest = if normalized == from {
+            ""
+        } else if normalized.starts_with(from) && normalized[from.len()..].starts_with('/') {
+            &original[from.len() + 1..]
+        } else {
+            continue;
+        };
+        
+        if best.map(|(len, _, _)| from.len() > len).unwrap_or(true) {
+            best = Some((from.len(), remap, rest));
+        }
+    }
+    
+    match best {
+        Some((_, remap, "")) => remap.to.clone(),
+        Some((_, remap, rest)) => remap.to.join(rest),
+        None => path.to_path_buf(),
+    }
+}
+
+// 源路径按映射表替换后的同步项目，恢复时写入替换后的位置
+pub fn remap_item(config: &Config, item: &SyncItem) -> SyncItem {
+    let mut item = item.clone();
+    item.source_path = remap_path(config, &item.source_path);
+    item
+}
+
+// 有路径被映射时显示恢复目标的预览并请求确认；没有映射时直接继续
+pub fn confirm_targets(config: &Config, targets: &[(&str, &Path)]) -> Result<bool> {
+    let remapped: Vec<(&str, &Path, PathBuf)> = targets
+        .iter()
+        .map(|(label, path)| (*label, *path, remap_path(config, path)))
+        .collect();
+    
+    if remapped.iter().all(|(_, path, new_path)| path == new_path) {
+        return Ok(true);
+    }
+    
+    println!("按路径映射表，恢复将写入以下位置:");
+    for (label, path, new_path) in &remapped {
+        if path == new_path {
+            println!("  {}: {:?}", label, path);
+        } else {
+            println!("  {}: {:?} -> {:?}", label, path, new_path);
+        }
+    }
+    
+    print!("请输入'yes'确认使用这些路径: ");
+    let mut input = String::new();
+    std::io::stdin().read_line(&mut input)?;
+    
+    Ok(input.trim().to_lowercase() == "yes")
+}
+
+// 查看、添加和删除路径映射
+pub fn manage_path_remaps(config: &mut Config) -> Result<()> {
+    println!("路径映射用于在安装位置不同的电脑上恢复：恢复时把旧的路径前缀替换为新的前缀。");
+    
+    if config.path_remaps.is_empty() {
+        println!("当前没有路径映射。");
+    } else {
+        println!("当前的路径映射:");
+        for (i, remap) in config.path_remaps.iter().enumerate() {
+            println!("  {}. {:?} -> {:?}", i + 1, remap.from, remap.to);
+        }
+    }
+    
+    println!("操作:");
+    println!("  1. 添加路径映射");
+    println!("  2. 删除路径映射");
+    
+    print!("请选择 (或输入0取消): ");
+    let mut input = String::new();
+    std::io::stdin().read_line(&mut input)?;
+    
+    match input.trim() {
+        "1" => {
+            print!("请输入旧的路径前缀 (如 C:\\Riot Games\\League of Legends): ");
+            let mut from = String::new();
+            std::io::stdin().read_line(&mut from)?;
+            
+            print!("请输入新的路径前缀 (如 D:\\Games\\League of Legends): ");
+            let mut to = String::new();
+            std::io::stdin().read_line(&mut to)?;
+            
+            let (from, to) = (from.trim(), to.trim());
+            if from.is_empty() || to.is_empty() {
+                println!("路径不能为空。操作已取消。");
+                return Ok(());
+            }
+            
+            // 同一个旧前缀只保留一条映射
+            let from = PathBuf::from(from);
+            config.path_remaps.retain(|r| normalize(&r.from.to_string_lossy()) != normalize(&from.to_string_lossy()));
+            config.path_remaps.push(PathRemap {
+                from: from.clone(),
+                to: PathBuf::from(to),
+            });
+            config.save()?;
+            
+            println!("已添加路径映射: {:?} -> {:?}", from, to);
+        },
+        "2" => {
+            if config.path_remaps.is_empty() {
+                println!("没有可以删除的路径映射。");
+                return Ok(());
+            }
+            
+            print!("请输入要删除的映射编号 (或输入0取消): ");
+            let mut input = String::new();
+            std::io::stdin().read_line(&mut input)?;
+            
+            match input.trim().parse::<usize>() {
+                Ok(num) if num > 0 && num <= config.path_remaps.len() => {
+                    let removed = config.path_remaps.remove(num - 1);
+                    config.save()?;
+                    println!("已删除路径映射: {:?} -> {:?}", removed.from, removed.to);
+                },
+                Ok(0) => {},
+                _ => println!("无效的选择。操作已取消。"),
+            }
+        },
+        "0" => {},
+        _ => println!("无效的选择。操作已取消。"),
+    }
+    
+    Ok(())
+}
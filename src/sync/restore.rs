@@ -0,0 +1,382 @@
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::config::Config;
+use crate::sync::{crypto, lock};
+
+// 安全快照存放在备份目录下的该子目录中
+const SAFETY_DIR_NAME: &str = "pre_restore";
+// 记录最近一次恢复操作的日志文件
+const JOURNAL_FILE_NAME: &str = "last_restore.json";
+
+#[derive(Debug, Serialize, Deserialize)]
+struct RestoreJournal {
+    timestamp: u64,
+    entries: Vec<JournalEntry>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct JournalEntry {
+    target: PathBuf,
+    // 恢复前目标不存在时为 None，撤销时直接删除目标
+    snapshot: Option<PathBuf>,
+}
+
+// 一次恢复操作：覆盖任何目标之前先为其拍摄安全快照，并记录到日志以便撤销
+pub struct RestoreSession<'a> {
+    config: &'a Config,
+    // 安全快照和日志所在的目录
+    root: PathBuf,
+    dir: PathBuf,
+    journal: RestoreJournal,
+}
+
+impl<'a> RestoreSession<'a> {
+    pub fn begin(config: &'a Config) -> Self {
+        Self::begin_at(config, safety_root(config))
+    }
+    
+    // 安全快照和日志放在 root 中的恢复操作，不影响用户可以撤销的上一次恢复
+    pub fn begin_at(config: &'a Config, root: PathBuf) -> Self {
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_secs();
+        
+        RestoreSession {
+            config,
+            dir: root.join(timestamp.to_string()),
+            root,
+            journal: RestoreJournal {
+                timestamp,
+                entries: Vec::new(),
+            },
+        }
+    }
+    
+    pub fn config(&self) -> &'a Config {
+        self.config
+    }
+    
+    // 为目标路径拍摄安全快照（同一目标只拍一次）
+    pub fn protect(&mut self, target: &Path) -> Result<()> {
+        if self.journal.entries.iter().any(|e| e.target == target) {
+            return Ok(());
+        }
+        
+        // 第一次写入时才清理上一次的安全快照，避免空操作覆盖可撤销的记录
+        if self.journal.entries.is_empty() {
+            clear_safety_snapshots(&self.root)?;
+            fs::create_dir_all(&self.dir)
+                .with_context(|| format!("创建安全快照目录失败: {:?}", self.dir))?;
+        }
+        
+        let snapshot = if target.exists() {
+            let staged = crypto::StagedSnapshot::new(self.config, &self.dir.join(self.journal.entries.len().to_string()))?;
+            copy_path(target, &staged.path())
+                .with_context(|| format!("为 {:?} 创建安全快照失败", target))?;
+            Some(staged.finish()?)
+        } else {
+            None
+        };
+        
+        self.journal.entries.push(JournalEntry {
+            target: target.to_path_buf(),
+            snapshot,
+        });
+        
+        // 每次都写入日志，中途失败时也能撤销已经完成的部分
+        save_journal(self.config, &self.root, &self.journal)
+    }
+    
+    // 拍摄安全快照后，以暂存再替换的方式把 from 恢复到 target
+    pub fn restore(&mut self, from: &Path, target: &Path) -> Result<()> {
+        self.protect(target)?;
+        replace_atomically(from, target)
+    }
+    
+    // 拍摄安全快照后，由 fill 在暂存路径中生成内容再替换 target
+    pub fn restore_with<F>(&mut self, target: &Path, fill: F) -> Result<()>
+    where
+        F: FnOnce(&Path) -> Result<()>,
+    {
+        self.protect(target)?;
+        replace_with(target, fill)
+    }
+}
+
+// 将文件或目录完整复制到 to（to 即复制后的路径）
+pub fn copy_path(from: &Path, to: &Path) -> Result<()> {
+    if from.is_dir() {
+        fs::create_dir_all(to).with_context(|| format!("创建目录失败: {:?}", to))?;
+        
+        let entries = fs::read_dir(from).with_context(|| format!("读取目录失败: {:?}", from))?;
+        for entry in entries {
+            let entry = entry?;
+            copy_path(&entry.path(), &to.join(entry.file_name()))?;
+        }
+    } else {
+        fs::copy(from, to).with_context(|| format!("从 {:?} 复制到 {:?} 失败", from, to))?;
+    }
+    
+    Ok(())
+}
+
+// 删除文件或目录，不存在时什么也不做
+pub fn remove_path(path: &Path) -> Result<()> {
+    if fs::symlink_metadata(path).is_err() {
+        return Ok(());
+    }
+    
+    // Windows 上只读文件无法删除，锁定过的内容先清除只读属性再重试
+    if delete(path).is_ok() {
+        return Ok(());
+    }
+    clear_readonly(path);
+    
+    delete(path).with_context(|| format!("删除失败: {:?}", path))
+}
+
+fn delete(path: &Path) -> std::io::Result<()> {
+    if path.is_dir() {
+        fs::remove_dir_all(path)
+    } else {
+        fs::remove_file(path)
+    }
+}
+
+// 递归清除只读属性，失败的条目留给后续的删除报告
+fn clear_readonly(path: &Path) {
+    if path.is_dir() {
+        if let Ok(entries) = fs::read_dir(path) {
+            for entry in entries.flatten() {
+                clear_readonly(&entry.path());
+            }
+        }
+    }
+    
+    if let Ok(metadata) = fs::symlink_metadata(path) {
+        let mut permissions = metadata.permissions();
+        if permissions.readonly() {
+            lock::apply_readonly(&mut permissions, false);
+            let _ = fs::set_permissions(path, permissions);
+        }
+    }
+}
+
+// 把 from 复制到 target 旁边的临时目录，完整后再替换 target
+pub fn replace_atomically(from: &Path, target: &Path) -> Result<()> {
+    replace_with(target, |staging| copy_path(from, staging))
+}
+
+// 由 fill 在临时路径中生成新内容，成功后再与 target 交换；
+// 任何一步失败时 target 保持原样
+pub fn replace_with<F>(target: &Path, fill: F) -> Result<()>
+where
+    F: FnOnce(&Path) -> Result<()>,
+{
+    let parent = target
+        .parent()
+        .ok_or_else(|| anyhow::anyhow!("无效的目标路径: {:?}", target))?;
+    let file_name = target
+        .file_name()
+        .ok_or_else(|| anyhow::anyhow!("无效的目标路径: {:?}", target))?
+        .to_string_lossy()
+        .to_string();
+    
+    fs::create_dir_all(parent).with_context(|| format!("创建父目录失败: {:?}", parent))?;
+    
+    let staging = parent.join(format!(".{}.restore-tmp", file_name));
+    let old = parent.join(format!(".{}.restore-old", file_name));
+    
+    // 清理上一次中断留下的临时文件
+    remove_path(&staging)?;
+    remove_path(&old)?;
+    
+    if let Err(e) = fill(&staging) {
+        let _ = remove_path(&staging);
+        return Err(e.context(format!("暂存恢复内容失败，{:?} 未被修改", target)));
+    }
+    
+    if fs::symlink_metadata(target).is_err() {
+        fs::rename(&staging, target)
+            .with_context(|| format!("将暂存内容移动到 {:?} 失败", target))?;
+        return Ok(());
+    }
+    
+    fs::rename(target, &old).with_context(|| format!("移走现有内容失败: {:?}", target))?;
+    
+    if let Err(e) = fs::rename(&staging, target) {
+        // 换入失败时放回原内容
+        let _ = fs::rename(&old, target);
+        let _ = remove_path(&staging);
+        return Err(anyhow::Error::new(e)
+            .context(format!("将暂存内容移动到 {:?} 失败，已还原原有内容", target)));
+    }
+    
+    if let Err(e) = remove_path(&old) {
+        println!("警告: 清理旧内容失败: {:#}", e);
+    }
+    
+    Ok(())
+}
+
+// 在备份中找到与目标路径对应的内容
+pub fn backup_payload(backup: &Path, target: &Path) -> PathBuf {
+    if !backup.is_dir() {
+        return backup.to_path_buf();
+    }
+    
+    if let Some(name) = target.file_name() {
+        let candidate = backup.join(name);
+        if candidate.exists() {
+            return candidate;
+        }
+    }
+    
+    // 兼容旧的同步项目备份：里面只有一个与目标同名的条目（名称大小写可能不同）时使用它；
+    // 名称不同的单个子目录是快照内容本身（如只有一个子目录的配置文件夹），不能当作外层目录
+    if let (Some(name), Ok(entries)) = (target.file_name(), fs::read_dir(backup)) {
+        let entry_paths: Vec<_> = entries.filter_map(Result::ok).map(|e| e.path()).collect();
+        let same_name = |path: &Path| {
+            path.file_name()
+                .map(|n| n.to_string_lossy().eq_ignore_ascii_case(&name.to_string_lossy()))
+                .unwrap_or(false)
+        };
+        if entry_paths.len() == 1 && same_name(&entry_paths[0]) {
+            return entry_paths[0].clone();
+        }
+    }
+    
+    backup.to_path_buf()
+}
+
+// 撤销最近一次恢复操作
+pub fn undo_last_restore(config: &Config) -> Result<()> {
+    let journal = match load_journal(config, &safety_root(config))? {
+        Some(journal) => journal,
+        None => {
+            println!("没有可以撤销的恢复操作。");
+            return Ok(());
+        }
+    };
+    
+    println!("最近一次恢复 (时间戳: {}) 修改了以下路径:", journal.timestamp);
+    for entry in &journal.entries {
+        let action = if entry.snapshot.is_some() { "还原" } else { "删除" };
+        println!("  [{}] {:?}", action, entry.target);
+    }
+    
+    print!("请输入'yes'确认撤销: ");
+    let mut input = String::new();
+    std::io::stdin().read_line(&mut input)?;
+    
+    if input.trim().to_lowercase() != "yes" {
+        println!("撤销操作已取消。");
+        return Ok(());
+    }
+    
+    for entry in journal.entries.iter().rev() {
+        match &entry.snapshot {
+            Some(snapshot) => {
+                replace_with(&entry.target, |staging| crypto::decrypt_copy(config, snapshot, staging))
+                    .with_context(|| {
+                        format!("从安全快照 {:?} 还原 {:?} 失败", snapshot, entry.target)
+                    })?;
+                println!("已还原: {:?}", entry.target);
+            },
+            None => {
+                remove_path(&entry.target)?;
+                println!("已删除: {:?}", entry.target);
+            },
+        }
+    }
+    
+    clear_safety_snapshots(&safety_root(config))?;
+    
+    println!("已撤销最近一次恢复操作！");
+    
+    Ok(())
+}
+
+fn safety_root(config: &Config) -> PathBuf {
+    config.backup_dir.join(SAFETY_DIR_NAME)
+}
+
+fn load_journal(config: &Config, root: &Path) -> Result<Option<RestoreJournal>> {
+    let journal_path = root.join(JOURNAL_FILE_NAME);
+    if !journal_path.exists() {
+        return Ok(None);
+    }
+    
+    let journal_str = crypto::read_to_string(config, &journal_path)
+        .with_context(|| format!("读取恢复日志失败: {:?}", journal_path))?;
+    let journal: RestoreJournal = serde_json::from_str(&journal_str)
+        .with_context(|| format!("解析恢复日志失败: {:?}", journal_path))?;
+    
+    if journal.entries.is_empty() {
+        return Ok(None);
+    }
+    
+    Ok(Some(journal))
+}
+
+fn save_journal(config: &Config, root: &Path, journal: &RestoreJournal) -> Result<()> {
+    let journal_path = root.join(JOURNAL_FILE_NAME);
+    let journal_str = serde_json::to_string_pretty(journal)
+        .with_context(|| "序列化恢复日志失败")?;
+    
+    crypto::write_file(config, &journal_path, journal_str.as_bytes())
+        .with_context(|| format!("写入恢复日志失败: {:?}", journal_path))?;
+    
+    Ok(())
+}
+
+fn clear_safety_snapshots(root: &Path) -> Result<()> {
+    remove_path(root)?;
+    fs::create_dir_all(root).with_context(|| format!("创建安全快照目录失败: {:?}", root))?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sync::snapshot::ScratchDir;
+    
+    fn touch(path: &Path) {
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(path, b"x").unwrap();
+    }
+    
+    #[test]
+    fn payload_uses_entry_named_like_target() {
+        let scratch = ScratchDir::new("payload_named");
+        let backup = scratch.path().join("MyItem_1");
+        touch(&backup.join("Config").join("game.cfg"));
+        
+        assert_eq!(backup_payload(&backup, Path::new("/games/Config")), backup.join("Config"));
+    }
+    
+    #[test]
+    fn payload_matches_legacy_entry_ignoring_case() {
+        let scratch = ScratchDir::new("payload_case");
+        let backup = scratch.path().join("MyItem_1");
+        touch(&backup.join("config").join("game.cfg"));
+        
+        // 不区分大小写的文件系统上直接按目标名称找到，路径的大小写可能与目录不同
+        let payload = backup_payload(&backup, Path::new("/games/Config"));
+        assert_eq!(payload.to_string_lossy().to_lowercase(), backup.join("config").to_string_lossy().to_lowercase());
+    }
+    
+    #[test]
+    fn payload_keeps_single_subfolder_of_content() {
+        let scratch = ScratchDir::new("payload_content");
+        let backup = scratch.path().join("game_config_1");
+        touch(&backup.join("Profiles").join("default.ini"));
+        
+        assert_eq!(backup_payload(&backup, Path::new("/games/Config")), backup);
+    }
+}
\ No newline at end of file
@@ -0,0 +1,327 @@
+use anyhow::Result;
+use std::collections::HashSet;
+
+use crate::config::{Config, RetentionPolicy};
+use crate::sync::sets;
+use crate::sync::snapshot::{self, Snapshot};
+
+// 某个系列的清理计划
+pub struct PrunePlan {
+    pub series: String,
+    pub keep: Vec<(Snapshot, Vec<&'static str>)>,
+    pub remove: Vec<Snapshot>,
+}
+
+// 计算系列实际生效的保留策略：项目 > 组 > 全局
+pub fn effective_policy(config: &Config, series: &str) -> Option<RetentionPolicy> {
+    if let Some(item) = config.sync_items.iter().find(|item| item.name == series) {
+        if let Some(policy) = &item.retention {
+            return Some(policy.clone());
+        }
+        
+        let group_policy = item
+            .group
+            .as_ref()
+            .and_then(|g| config.sync_groups.iter().find(|group| &group.name == g))
+            .and_then(|group| group.retention.clone());
+        if group_policy.is_some() {
+            return group_policy;
+        }
+    }
+    
+    config.retention.clone()
+}
+
+fn has_rules(policy: &RetentionPolicy) -> bool {
+    policy.keep_last.is_some()
+        || policy.keep_daily.is_some()
+        || policy.keep_weekly.is_some()
+        || policy.keep_monthly.is_some()
+        || policy.max_total_size_mb.is_some()
+}
+
+// 黄金快照是漂移检查的基准，不能被清理
+fn is_golden(config: &Config, series: &str, snap: &Snapshot) -> bool {
+    let name = snap.file_name();
+    config
+        .sync_items
+        .iter()
+        .any(|item| item.name == series && item.golden.as_ref().is_some_and(|g| g.snapshot == name))
+}
+
+// 根据保留策略计算一个系列中要保留和删除的快照
+pub fn plan_series(config: &Config, series: &str) -> Result<Option<PrunePlan>> {
+    let policy = match effective_policy(config, series) {
+        Some(policy) if has_rules(&policy) => policy,
+        _ => return Ok(None),
+    };
+    
+    // 最新的在前
+    let snapshots = snapshot::list_snapshots(config, series)?;
+    let mut reasons: Vec<Vec<&'static str>> = vec![Vec::new(); snapshots.len()];
+    
+    // 最新的快照集需要保持完整，才能把组作为一个整体恢复
+    let set_snapshots = sets::latest_set_snapshots(config)?;
+    
+    for (i, snap) in snapshots.iter().enumerate() {
+        if snapshot::load_meta(config, snap)?.pinned {
+            reasons[i].push("已固定");
+        }
+        if is_golden(config, series, snap) {
+            reasons[i].push("黄金快照");
+        }
+        if set_snapshots.contains(&snap.file_name()) {
+            reasons[i].push("最新快照集");
+        }
+    }
+    
+    if !snapshots.is_empty() {
+        reasons[0].push("最新");
+    }
+    
+    if let Some(n) = policy.keep_last {
+        for reason in reasons.iter_mut().take(n) {
+            reason.push("最近");
+        }
+    }
+    
+    keep_by_bucket(&snapshots, &mut reasons, policy.keep_daily, "每日", |ts| ts / 86400);
+    keep_by_bucket(&snapshots, &mut reasons, policy.keep_weekly, "每周", |ts| (ts / 86400 + 3) / 7);
+    keep_by_bucket(&snapshots, &mut reasons, policy.keep_monthly, "每月", month_of);
+    
+    // 只配置了容量上限时，先假设全部保留，再按容量裁剪
+    let only_size_limit = policy.keep_last.is_none()
+        && policy.keep_daily.is_none()
+        && policy.keep_weekly.is_none()
+        && policy.keep_monthly.is_none();
+    if only_size_limit {
+        for reason in reasons.iter_mut().filter(|r| r.is_empty()) {
+            reason.push("容量内");
+        }
+    }
+    
+    if let Some(max_mb) = policy.max_total_size_mb {
+        let limit = max_mb * 1024 * 1024;
+        let mut total = 0u64;
+        
+        for (i, snap) in snapshots.iter().enumerate() {
+            if reasons[i].is_empty() {
+                continue;
+            }
+            
+            total += snapshot::path_size(&snap.path);
+            
+            // 固定的、黄金的、最新快照集中的和最新的快照不受容量限制
+            let protected = reasons[i].contains(&"已固定")
+                || reasons[i].contains(&"黄金快照")
+                || reasons[i].contains(&"最新快照集")
+                || i == 0;
+            if total > limit && !protected {
+                reasons[i].clear();
+            }
+        }
+    }
+    
+    let mut plan = PrunePlan {
+        series: series.to_string(),
+        keep: Vec::new(),
+        remove: Vec::new(),
+    };
+    
+    for (snap, reason) in snapshots.into_iter().zip(reasons) {
+        if reason.is_empty() {
+            plan.remove.push(snap);
+        } else {
+            plan.keep.push((snap, reason));
+        }
+    }
+    
+    Ok(Some(plan))
+}
+
+// 在每个时间段内保留最新的一个快照，最多保留 count 个时间段
+fn keep_by_bucket<F>(
+    snapshots: &[Snapshot],
+    reasons: &mut [Vec<&'static str>],
+    count: Option<usize>,
+    label: &'static str,
+    bucket_of: F,
+) where
+    F: Fn(u64) -> u64,
+{
+    let count = match count {
+        Some(count) => count,
+        None => return,
+    };
+    
+    let mut seen = HashSet::new();
+    for (i, snap) in snapshots.iter().enumerate() {
+        if seen.len() >= count {
+            break;
+        }
+        if seen.insert(bucket_of(snap.timestamp)) {
+            reasons[i].push(label);
+        }
+    }
+}
+
+// 把 Unix 时间戳换算成 "年*12+月" 形式的月份编号（UTC）
+fn month_of(timestamp: u64) -> u64 {
+    let days = (timestamp / 86400) as i64 + 719468;
+    let era = days.div_euclid(146097);
+    let doe = days.rem_euclid(146097);
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+    
+    (year * 12 + month - 1) as u64
+}
+
+// 按保留策略清理备份目录；dry_run 时只列出将被删除的快照
+pub fn prune_backups(config: &Config, dry_run: bool) -> Result<()> {
+    let mut plans = Vec::new();
+    
+    for series in snapshot::all_series(config) {
+        if let Some(plan) = plan_series(config, &series)? {
+            plans.push(plan);
+        }
+    }
+    
+    if plans.is_empty() {
+        println!("没有配置任何保留策略，所有备份都会保留。");
+        return Ok(());
+    }
+    
+    let mut total_count = 0;
+    let mut total_size = 0u64;
+    
+    for plan in &plans {
+        println!("{}: 保留 {} 个，删除 {} 个", plan.series, plan.keep.len(), plan.remove.len());
+        
+        for (snap, reason) in &plan.keep {
+            println!("  保留 {} ({})", snap.file_name(), reason.join(", "));
+        }
+        
+        for snap in &plan.remove {
+            let size = snapshot::path_size(&snap.path);
+            total_size += size;
+            total_count += 1;
+            println!("  删除 {} ({:.2} MB)", snap.file_name(), size as f64 / 1024.0 / 1024.0);
+        }
+    }
+    
+    println!(
+        "\n共 {} 个快照将被删除，释放 {:.2} MB。",
+        total_count,
+        total_size as f64 / 1024.0 / 1024.0
+    );
+    
+    if dry_run || total_count == 0 {
+        return Ok(());
+    }
+    
+    print!("请输入'yes'确认删除: ");
+    let mut input = String::new();
+    std::io::stdin().read_line(&mut input)?;
+    
+    if input.trim().to_lowercase() != "yes" {
+        println!("清理操作已取消。");
+        return Ok(());
+    }
+    
+    for plan in &plans {
+        for snap in &plan.remove {
+            snapshot::remove_snapshot(config, snap)?;
+            println!("已删除: {:?}", snap.path);
+        }
+    }
+    
+    sets::remove_orphaned_sets(config)?;
+    
+    println!("备份清理完成！");
+    
+    Ok(())
+}
+
+// 读取一个可选的数字，直接回车表示不限制
+fn read_optional_number(prompt: &str) -> Result<Option<u64>> {
+    print!("{} (直接回车表示不限制): ", prompt);
+    let mut input = String::new();
+    std::io::stdin().read_line(&mut input)?;
+    
+    Ok(input.trim().parse().ok())
+}
+
+// 为全局、组或项目设置保留策略
+pub fn set_retention_policy(config: &mut Config) -> Result<()> {
+    println!("要为哪一级设置保留策略?");
+    println!("  1. 全局（包括游戏配置和脚本备份）");
+    println!("  2. 同步组");
+    println!("  3. 同步项目");
+    
+    print!("请选择 (或输入0取消): ");
+    let mut input = String::new();
+    std::io::stdin().read_line(&mut input)?;
+    
+    let level = input.trim().to_string();
+    let target_idx = match level.as_str() {
+        "1" => None,
+        "2" | "3" => {
+            let names: Vec<String> = if level == "2" {
+                config.sync_groups.iter().map(|g| g.name.clone()).collect()
+            } else {
+                config.sync_items.iter().map(|i| i.name.clone()).collect()
+            };
+            
+            if names.is_empty() {
+                println!("没有可以设置的对象。");
+                return Ok(());
+            }
+            
+            for (i, name) in names.iter().enumerate() {
+                println!("  {}. {}", i + 1, name);
+            }
+            
+            print!("请输入编号 (或输入0取消): ");
+            let mut input = String::new();
+            std::io::stdin().read_line(&mut input)?;
+            
+            match input.trim().parse::<usize>() {
+                Ok(num) if num > 0 && num <= names.len() => Some(num - 1),
+                Ok(0) => return Ok(()),
+                _ => {
+                    println!("无效的选择。操作已取消。");
+                    return Ok(());
+                }
+            }
+        },
+        "0" => return Ok(()),
+        _ => {
+            println!("无效的选择。操作已取消。");
+            return Ok(());
+        }
+    };
+    
+    println!("请输入新的保留规则，所有规则都留空表示使用上一级的策略。");
+    let policy = RetentionPolicy {
+        keep_last: read_optional_number("保留最近的快照数")?.map(|n| n as usize),
+        keep_daily: read_optional_number("保留每日快照的天数")?.map(|n| n as usize),
+        keep_weekly: read_optional_number("保留每周快照的周数")?.map(|n| n as usize),
+        keep_monthly: read_optional_number("保留每月快照的月数")?.map(|n| n as usize),
+        max_total_size_mb: read_optional_number("总容量上限 (MB)")?,
+    };
+    let policy = if has_rules(&policy) { Some(policy) } else { None };
+    
+    match (level.as_str(), target_idx) {
+        ("2", Some(idx)) => config.sync_groups[idx].retention = policy,
+        ("3", Some(idx)) => config.sync_items[idx].retention = policy,
+        _ => config.retention = policy,
+    }
+    
+    config.save()?;
+    println!("保留策略已更新。可以使用\"预览清理\"查看效果。");
+    
+    Ok(())
+}
\ No newline at end of file
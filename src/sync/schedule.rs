@@ -0,0 +1,389 @@
+use anyhow::{Context, Result};
+use chrono::{DateTime, Local, TimeZone};
+use cron::Schedule;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::str::FromStr;
+use std::sync::atomic::Ordering;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::config::{get_schedule_state_path, BackupSchedule, Config, SyncGroup};
+use crate::sync::snapshot::SnapshotLabel;
+use crate::sync::{backup_group_by_name, drift};
+use crate::sync::process::{GameEvent, GameMonitor};
+use crate::sync::watch::{log, stop_on_enter};
+
+// 守护模式下检查计划的间隔
+const POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+// 每个组最后一次计划备份的时间（Unix 秒）
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct ScheduleState {
+    #[serde(default)]
+    last_run: HashMap<String, i64>,
+}
+
+fn load_state() -> Result<ScheduleState> {
+    let path = get_schedule_state_path();
+    if !path.exists() {
+        return Ok(ScheduleState::default());
+    }
+    
+    let state_str = fs::read_to_string(&path)
+        .with_context(|| format!("读取计划状态失败: {:?}", path))?;
+    let state = serde_json::from_str(&state_str)
+        .with_context(|| format!("解析计划状态失败: {:?}", path))?;
+    
+    Ok(state)
+}
+
+fn save_state(state: &ScheduleState) -> Result<()> {
+    let path = get_schedule_state_path();
+    let state_str = serde_json::to_string_pretty(state)
+        .with_context(|| "序列化计划状态失败")?;
+    fs::write(&path, state_str)
+        .with_context(|| format!("写入计划状态失败: {:?}", path))?;
+    
+    Ok(())
+}
+
+// 解析 cron 表达式；接受标准的五段式（分 时 日 月 周），也接受带秒的六段式
+fn parse_cron(expression: &str) -> Result<Schedule> {
+    let expression = expression.trim();
+    let fields: Vec<&str> = expression.split_whitespace().collect();
+    let full = if fields.len() == 5 {
+        format!("0 {} {}", fields[..4].join(" "), weekday_names(fields[4]))
+    } else {
+        expression.to_string()
+    };
+    
+    Schedule::from_str(&full).map_err(|e| anyhow::anyhow!("无效的 cron 表达式 '{}': {}", expression, e))
+}
+
+// 标准 cron 中星期用 0-7 表示（0 和 7 都是周日），解析库使用 1=周日，统一换成英文缩写避免歧义
+fn weekday_names(field: &str) -> String {
+    const NAMES: [&str; 8] = ["SUN", "MON", "TUE", "WED", "THU", "FRI", "SAT", "SUN"];
+    
+    field
+        .split(',')
+        .map(|part| {
+            // 步长（/ 之后的数字）保持不变
+            let (range, step) = match part.split_once('/') {
+                Some((range, step)) => (range, Some(step)),
+                None => (part, None),
+            };
+            
+            let range = range
+                .split('-')
+                .map(|day| match day.parse::<usize>() {
+                    Ok(n) if n < NAMES.len() => NAMES[n].to_string(),
+                    _ => day.to_string(),
+                })
+                .collect::<Vec<_>>()
+                .join("-");
+            
+            match step {
+                Some(step) => format!("{}/{}", range, step),
+                None => range,
+            }
+        })
+        .collect::<Vec<_>>()
+        .join(",")
+}
+
+pub fn describe(schedule: &BackupSchedule) -> String {
+    match schedule {
+        BackupSchedule::Interval { minutes } => format!("每 {} 分钟", minutes),
+        BackupSchedule::Cron { expression } => format!("cron '{}'", expression),
+    }
+}
+
+// 计算下一次应当运行的时间；从未运行过的计划立即运行
+fn next_run(schedule: &BackupSchedule, last_run: Option<i64>) -> Result<DateTime<Local>> {
+    let last = match last_run.and_then(|ts| Local.timestamp_opt(ts, 0).single()) {
+        Some(last) => last,
+        None => return Ok(Local::now()),
+    };
+    
+    match schedule {
+        BackupSchedule::Interval { minutes } => {
+            Ok(last + chrono::Duration::minutes((*minutes).max(1) as i64))
+        },
+        BackupSchedule::Cron { expression } => parse_cron(expression)?
+            .after(&last)
+            .next()
+            .ok_or_else(|| anyhow::anyhow!("cron 表达式 '{}' 之后不会再触发", expression)),
+    }
+}
+
+// 启用且设置了有效计划的组
+fn scheduled_groups(config: &Config) -> Vec<(&SyncGroup, &BackupSchedule)> {
+    let mut groups = Vec::new();
+    
+    for group in config.sync_groups.iter().filter(|g| g.is_enabled) {
+        if let Some(schedule) = &group.schedule {
+            if let BackupSchedule::Cron { expression } = schedule {
+                if let Err(e) = parse_cron(expression) {
+                    println!("警告: 组 '{}' 的计划无效，已跳过: {:#}", group.name, e);
+                    continue;
+                }
+            }
+            groups.push((group, schedule));
+        }
+    }
+    
+    groups
+}
+
+// 已经到期、现在应当备份的组；游戏运行期间到期的组记录在 deferred 中，等游戏退出后再备份
+fn due_groups<'a>(
+    groups: &[(&'a SyncGroup, &'a BackupSchedule)],
+    state: &ScheduleState,
+    now: DateTime<Local>,
+    game_running: bool,
+    deferred: &mut HashSet<String>,
+) -> Result<Vec<(&'a SyncGroup, &'a BackupSchedule)>> {
+    let mut due = Vec::new();
+    
+    for &(group, schedule) in groups {
+        let next = next_run(schedule, state.last_run.get(&group.name).copied())?;
+        if next > now {
+            continue;
+        }
+        
+        if game_running {
+            if deferred.insert(group.name.clone()) {
+                log(&format!("游戏正在运行，推迟组 '{}' 的计划备份", group.name));
+            }
+            continue;
+        }
+        deferred.remove(&group.name);
+        
+        due.push((group, schedule));
+    }
+    
+    Ok(due)
+}
+
+// 守护模式：按各组的计划自动备份，启动时补做睡眠或关机期间错过的备份；按回车键停止
+pub fn run_daemon(config: &Config) -> Result<()> {
+    let groups = scheduled_groups(config);
+    let golden_items = drift::golden_items(config);
+    if groups.is_empty() && golden_items.is_empty() {
+        println!("没有设置了备份计划的启用组，也没有设置了黄金快照的项目。可以在组管理中设置备份计划。");
+        return Ok(());
+    }
+    
+    let mut state = load_state()?;
+    let now = Local::now();
+    
+    for (group, schedule) in &groups {
+        let last_run = state.last_run.get(&group.name).copied();
+        let next = next_run(schedule, last_run)?;
+        
+        if last_run.is_some() && next <= now {
+            log(&format!(
+                "组 '{}' 错过了 {} 的计划备份，将立即补做",
+                group.name,
+                next.format("%Y-%m-%d %H:%M")
+            ));
+        } else {
+            log(&format!(
+                "组 '{}' ({}) 下次备份: {}",
+                group.name,
+                describe(schedule),
+                next.format("%Y-%m-%d %H:%M")
+            ));
+        }
+    }
+    
+    // 游戏运行期间推迟到期的备份，避免复制到写了一半的文件
+    let mut monitor = GameMonitor::for_config(config);
+    let mut deferred = HashSet::new();
+    
+    let drift_interval = Duration::from_secs(config.drift_check_minutes.max(1) * 60);
+    let mut last_drift_check: Option<Instant> = None;
+    if !golden_items.is_empty() {
+        log(&format!("每 {} 分钟检查一次黄金快照漂移", drift_interval.as_secs() / 60));
+    }
+    
+    let stop = stop_on_enter();
+    println!("守护模式已启动，按回车键停止。");
+    
+    while !stop.load(Ordering::SeqCst) {
+        let now = Local::now();
+        
+        if last_drift_check.map(|t| t.elapsed() >= drift_interval).unwrap_or(true) {
+            for item in &golden_items {
+                drift::check_and_log(config, item);
+            }
+            last_drift_check = Some(Instant::now());
+        }
+        
+        match monitor.poll() {
+            GameEvent::Started(names) => log(&format!("检测到游戏运行: {}", names.join(", "))),
+            GameEvent::Exited if !deferred.is_empty() => log("游戏已退出，开始执行推迟的计划备份"),
+            _ => {},
+        }
+        
+        for (group, schedule) in due_groups(&groups, &state, now, monitor.is_running(), &mut deferred)? {
+            log(&format!("开始计划备份组 '{}'", group.name));
+            
+            // 失败也记录本次运行，避免每秒重试；错误只记录日志，守护模式继续运行
+            if let Err(e) = backup_group_by_name(config, &group.name, &SnapshotLabel::default()) {
+                log(&format!("计划备份组 '{}' 失败: {:#}", group.name, e));
+            }
+            
+            state.last_run.insert(group.name.clone(), now.timestamp());
+            save_state(&state)?;
+            
+            let next = next_run(schedule, Some(now.timestamp()))?;
+            log(&format!("组 '{}' 下次备份: {}", group.name, next.format("%Y-%m-%d %H:%M")));
+        }
+        
+        thread::sleep(POLL_INTERVAL);
+    }
+    
+    log("守护模式已停止");
+    
+    Ok(())
+}
+
+// 为同步组设置自动备份计划
+pub fn set_group_schedule(config: &mut Config) -> Result<()> {
+    if config.sync_groups.is_empty() {
+        println!("没有同步组可以设置。");
+        return Ok(());
+    }
+    
+    println!("同步组列表:");
+    for (i, group) in config.sync_groups.iter().enumerate() {
+        let schedule = group.schedule.as_ref().map(describe).unwrap_or_else(|| "无计划".to_string());
+        println!("  {}. {} [{}]", i + 1, group.name, schedule);
+    }
+    
+    print!("请输入要设置计划的组编号 (或输入0取消): ");
+    let mut input = String::new();
+    std::io::stdin().read_line(&mut input)?;
+    
+    let selection: usize = match input.trim().parse() {
+        Ok(num) if num > 0 && num <= config.sync_groups.len() => num - 1,
+        Ok(0) => return Ok(()),
+        _ => {
+            println!("无效的选择。操作已取消。");
+            return Ok(());
+        }
+    };
+    
+    println!("计划类型:");
+    println!("  1. 固定间隔");
+    println!("  2. cron 表达式");
+    println!("  3. 取消计划");
+    
+    print!("请选择: ");
+    let mut input = String::new();
+    std::io::stdin().read_line(&mut input)?;
+    
+    let schedule = match input.trim() {
+        "1" => {
+            print!("请输入间隔分钟数: ");
+            let mut input = String::new();
+            std::io::stdin().read_line(&mut input)?;
+            
+            match input.trim().parse::<u64>() {
+                Ok(minutes) if minutes > 0 => Some(BackupSchedule::Interval { minutes }),
+                _ => {
+                    println!("无效的分钟数。操作已取消。");
+                    return Ok(());
+                }
+            }
+        },
+        "2" => {
+            println!("格式为 \"分 时 日 月 周\"，例如 \"0 3 * * *\" 表示每天凌晨 3 点。");
+            print!("请输入 cron 表达式: ");
+            let mut input = String::new();
+            std::io::stdin().read_line(&mut input)?;
+            
+            let expression = input.trim().to_string();
+            if let Err(e) = parse_cron(&expression) {
+                println!("{:#}", e);
+                return Ok(());
+            }
+            
+            Some(BackupSchedule::Cron { expression })
+        },
+        "3" => None,
+        _ => {
+            println!("无效的选择。操作已取消。");
+            return Ok(());
+        }
+    };
+    
+    let group = &mut config.sync_groups[selection];
+    group.schedule = schedule;
+    let group_name = group.name.clone();
+    let description = group.schedule.as_ref().map(describe);
+    
+    config.save()?;
+    
+    match description {
+        Some(description) => println!("已将组 '{}' 的计划设为 {}，在守护模式下运行。", group_name, description),
+        None => println!("已取消组 '{}' 的计划。", group_name),
+    }
+    
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sync::process::FakeProbe;
+    
+    fn group(name: &str) -> SyncGroup {
+        SyncGroup {
+            name: name.to_string(),
+            description: None,
+            is_enabled: true,
+            retention: None,
+            storage_mode: None,
+            schedule: None,
+        }
+    }
+    
+    #[test]
+    fn due_backups_wait_for_game_exit() {
+        let hourly = BackupSchedule::Interval { minutes: 60 };
+        let (overdue, recent) = (group("overdue"), group("recent"));
+        let groups = vec![(&overdue, &hourly), (&recent, &hourly)];
+        
+        let now = Local::now();
+        let mut state = ScheduleState::default();
+        state.last_run.insert("overdue".to_string(), now.timestamp() - 2 * 3600);
+        state.last_run.insert("recent".to_string(), now.timestamp() - 60);
+        
+        let probe = FakeProbe {
+            running: vec!["League of Legends.exe".to_string()],
+            exit_after: Some(2),
+            ..FakeProbe::default()
+        };
+        let mut monitor = GameMonitor::new(Box::new(probe), vec!["League of Legends.exe".to_string()]);
+        let mut deferred = HashSet::new();
+        
+        // 游戏运行期间到期的备份被推迟，多次检查也只记录一次
+        assert!(matches!(monitor.poll(), GameEvent::Started(_)));
+        assert!(due_groups(&groups, &state, now, monitor.is_running(), &mut deferred).unwrap().is_empty());
+        assert!(matches!(monitor.poll(), GameEvent::NoChange));
+        assert!(due_groups(&groups, &state, now, monitor.is_running(), &mut deferred).unwrap().is_empty());
+        assert_eq!(deferred.len(), 1);
+        assert!(deferred.contains("overdue"));
+        
+        // 游戏退出后只备份到期的组
+        assert!(matches!(monitor.poll(), GameEvent::Exited));
+        let due = due_groups(&groups, &state, now, monitor.is_running(), &mut deferred).unwrap();
+        assert_eq!(due.len(), 1);
+        assert_eq!(due[0].0.name, "overdue");
+        assert!(deferred.is_empty());
+    }
+}
\ No newline at end of file
@@ -0,0 +1,246 @@
+use anyhow::{Context, Result};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::config::{Config, SyncItem};
+use crate::sync::browse::extract_to_source;
+use crate::sync::filter::PathFilter;
+use crate::sync::restore::backup_payload;
+use crate::sync::snapshot::{self, Snapshot};
+use crate::sync::{index, restore_item_from};
+
+// 内容搜索跳过超过该大小的文件
+const MAX_CONTENT_SIZE: u64 = 10 * 1024 * 1024;
+// 每个文件最多显示的匹配行数
+const MAX_LINES_PER_FILE: usize = 3;
+
+// 一个匹配的文件，路径相对于快照内容的根目录；单文件项目的路径为空
+struct FileHit {
+    path: String,
+    // (行号, 行内容)，只按文件名搜索时为空
+    lines: Vec<(usize, String)>,
+}
+
+// 一个快照中的所有匹配
+struct SnapshotHit {
+    series: String,
+    snapshot: Snapshot,
+    files: Vec<FileHit>,
+}
+
+// 在文本文件中查找包含 text 的行（不区分大小写）；二进制文件和过大的文件跳过
+fn find_lines(path: &Path, text: &str) -> Vec<(usize, String)> {
+    let too_large = fs::metadata(path).map(|m| m.len() > MAX_CONTENT_SIZE).unwrap_or(true);
+    if too_large {
+        return Vec::new();
+    }
+    
+    let bytes = match fs::read(path) {
+        Ok(bytes) if !bytes.contains(&0) => bytes,
+        _ => return Vec::new(),
+    };
+    
+    let needle = text.to_lowercase();
+    String::from_utf8_lossy(&bytes)
+        .lines()
+        .enumerate()
+        .filter(|(_, line)| line.to_lowercase().contains(&needle))
+        .map(|(i, line)| (i + 1, line.trim().to_string()))
+        .collect()
+}
+
+// 在一个快照中按文件名和内容搜索；两个条件都给出时文件需要同时满足
+fn search_snapshot(
+    config: &Config,
+    root_hint: Option<&Path>,
+    snap: &Snapshot,
+    name_filter: Option<&PathFilter>,
+    text: Option<&str>,
+) -> Result<Vec<FileHit>> {
+    let content = snapshot::open_snapshot(config, &snap.path)?;
+    let root = match root_hint {
+        Some(source) => backup_payload(content.path(), source),
+        None => content.path().to_path_buf(),
+    };
+    
+    let mut hits = Vec::new();
+    for rel in index::list_tree(&root)? {
+        let path = index::file_in(&root, &rel);
+        
+        if let Some(filter) = name_filter {
+            let name_rel = if rel.is_empty() { PathBuf::from(path.file_name().unwrap_or_default()) } else { PathBuf::from(&rel) };
+            if !filter.is_included(&name_rel, false) {
+                continue;
+            }
+        }
+        
+        let lines = match text {
+            Some(text) => {
+                let lines = find_lines(&path, text);
+                if lines.is_empty() {
+                    continue;
+                }
+                lines
+            },
+            None => Vec::new(),
+        };
+        
+        hits.push(FileHit { path: rel, lines });
+    }
+    
+    Ok(hits)
+}
+
+// 快照内容对应的源路径：同步项目按项目源路径定位内容，游戏配置按配置路径，脚本直接使用快照根目录
+fn source_of<'a>(config: &'a Config, series: &str) -> Option<&'a Path> {
+    if let Some(item) = config.sync_items.iter().find(|item| item.name == series) {
+        return Some(&item.source_path);
+    }
+    if series == snapshot::GAME_CONFIG_SERIES {
+        return Some(&config.game_config_path);
+    }
+    None
+}
+
+fn print_hits(config: &Config, hits: &[SnapshotHit]) {
+    let mut series = "";
+    for (i, hit) in hits.iter().enumerate() {
+        if hit.series != series {
+            series = &hit.series;
+            println!("\n[{}]", series);
+        }
+        
+        println!(
+            "  {}. {}{} ({} 个文件)",
+            i + 1,
+            hit.snapshot.file_name(),
+            snapshot::describe(config, &hit.snapshot.path),
+            hit.files.len()
+        );
+        for file in &hit.files {
+            let display = if file.path.is_empty() { hit.series.as_str() } else { file.path.as_str() };
+            println!("       {}", display);
+            for (line_no, line) in file.lines.iter().take(MAX_LINES_PER_FILE) {
+                println!("         {}: {}", line_no, line);
+            }
+            if file.lines.len() > MAX_LINES_PER_FILE {
+                println!("         ... 另有 {} 行匹配", file.lines.len() - MAX_LINES_PER_FILE);
+            }
+        }
+    }
+}
+
+// 从搜索结果直接恢复：只提取匹配的文件，或把整个项目恢复到该快照
+fn restore_from_hit(config: &Config, item: &SyncItem, hit: &SnapshotHit) -> Result<()> {
+    println!("从快照 {} 恢复 {}:", hit.snapshot.file_name(), item.name);
+    println!("  1. 只提取匹配的 {} 个文件到原位置", hit.files.len());
+    println!("  2. 把整个项目恢复到该快照");
+    
+    print!("请选择 (或输入0取消): ");
+    let mut input = String::new();
+    std::io::stdin().read_line(&mut input)?;
+    
+    match input.trim() {
+        "1" => {
+            let content = snapshot::open_snapshot(config, &hit.snapshot.path)?;
+            let payload = backup_payload(content.path(), &item.source_path);
+            let paths: Vec<String> = hit.files.iter().map(|f| f.path.clone()).collect();
+            extract_to_source(config, item, &hit.snapshot.path, &payload, &paths)
+        },
+        "2" => restore_item_from(config, item, &hit.snapshot.path),
+        "0" => Ok(()),
+        _ => {
+            println!("无效的选择。操作已取消。");
+            Ok(())
+        }
+    }
+}
+
+// 按文件名通配符和文本内容搜索备份目录中的所有快照，结果按项目和快照分组
+pub fn search_snapshots(config: &Config) -> Result<()> {
+    print!("请输入文件名通配符，如 *.json 或 input.ini (直接回车表示不限): ");
+    let mut input = String::new();
+    std::io::stdin().read_line(&mut input)?;
+    let pattern = input.trim().to_string();
+    
+    print!("请输入要搜索的文本内容 (直接回车表示只按文件名搜索): ");
+    let mut input = String::new();
+    std::io::stdin().read_line(&mut input)?;
+    let text = input.trim().to_string();
+    
+    if pattern.is_empty() && text.is_empty() {
+        println!("没有输入搜索条件。操作已取消。");
+        return Ok(());
+    }
+    
+    // 通配符使用与项目过滤规则相同的语法：不含 '/' 时匹配任意目录下的文件名
+    let name_filter = if pattern.is_empty() {
+        None
+    } else {
+        Some(PathFilter::new(std::slice::from_ref(&pattern), &[]).with_context(|| format!("无效的通配符: {}", pattern))?)
+    };
+    let text = Some(text.as_str()).filter(|t| !t.is_empty());
+    
+    println!("正在搜索...");
+    
+    let mut hits = Vec::new();
+    let mut failed = 0;
+    for series in snapshot::all_series(config) {
+        let source = source_of(config, &series);
+        for snap in snapshot::list_snapshots(config, &series)? {
+            // 单个快照无法打开（如密码错误）时跳过，继续搜索其他快照
+            match search_snapshot(config, source, &snap, name_filter.as_ref(), text) {
+                Ok(files) if !files.is_empty() => hits.push(SnapshotHit {
+                    series: series.clone(),
+                    snapshot: snap,
+                    files,
+                }),
+                Ok(_) => {},
+                Err(e) => {
+                    failed += 1;
+                    println!("警告: 无法搜索快照 {}: {:#}", snap.file_name(), e);
+                },
+            }
+        }
+    }
+    
+    if hits.is_empty() {
+        println!("没有找到匹配的快照。");
+        if failed > 0 {
+            println!("{} 个快照无法打开，已跳过。", failed);
+        }
+        return Ok(());
+    }
+    
+    print_hits(config, &hits);
+    
+    let file_count: usize = hits.iter().map(|h| h.files.len()).sum();
+    println!("\n共在 {} 个快照中找到 {} 个匹配的文件。", hits.len(), file_count);
+    if failed > 0 {
+        println!("{} 个快照无法打开，已跳过。", failed);
+    }
+    
+    print!("输入结果编号从该快照恢复 (直接回车返回): ");
+    let mut input = String::new();
+    std::io::stdin().read_line(&mut input)?;
+    
+    if input.trim().is_empty() {
+        return Ok(());
+    }
+    
+    let hit = match input.trim().parse::<usize>() {
+        Ok(num) if num > 0 && num <= hits.len() => &hits[num - 1],
+        _ => {
+            println!("无效的选择。操作已取消。");
+            return Ok(());
+        }
+    };
+    
+    match config.sync_items.iter().find(|item| item.name == hit.series) {
+        Some(item) => restore_from_hit(config, item, hit),
+        None => {
+            println!("{} 的快照请在对应的恢复菜单中恢复。", hit.series);
+            Ok(())
+        }
+    }
+}
\ No newline at end of file
@@ -0,0 +1,273 @@
+use anyhow::{Context, Result};
+use chrono::{Local, TimeZone};
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::config::{Config, SyncItem};
+use crate::sync::restore::RestoreSession;
+use crate::sync::{crypto, process, remap, restore_item, validate, version};
+
+// 快照集记录存放在备份目录下的该子目录中
+const SETS_DIR_NAME: &str = "snapshot_sets";
+
+// 快照集中一个项目使用的快照
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SetMember {
+    pub item: String,
+    // 备份目录中的快照名称；项目未变化时是沿用的旧快照
+    pub snapshot: String,
+}
+
+// 一次组备份或全部备份得到的一组快照，恢复时作为一个整体使用
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SnapshotSet {
+    pub id: String,
+    // 为空表示一键备份所有启用的项目
+    pub group: Option<String>,
+    pub timestamp: u64,
+    pub members: Vec<SetMember>,
+}
+
+impl SnapshotSet {
+    pub fn scope(&self) -> String {
+        match &self.group {
+            Some(group) => format!("组 '{}'", group),
+            None => "所有启用的项目".to_string(),
+        }
+    }
+    
+    pub fn member(&self, item_name: &str) -> Option<&SetMember> {
+        self.members.iter().find(|m| m.item == item_name)
+    }
+}
+
+fn sets_dir(config: &Config) -> PathBuf {
+    config.backup_dir.join(SETS_DIR_NAME)
+}
+
+// 记录一次备份得到的快照集，返回快照集 ID；与同一范围上一个快照集相同时沿用它
+pub fn save_set(config: &Config, group: Option<&str>, timestamp: u64, members: Vec<SetMember>) -> Result<String> {
+    if let Some(previous) = list_sets(config)?.into_iter().find(|set| set.group.as_deref() == group) {
+        if previous.members == members {
+            return Ok(previous.id);
+        }
+    }
+    
+    let dir = sets_dir(config);
+    fs::create_dir_all(&dir).with_context(|| format!("创建快照集目录失败: {:?}", dir))?;
+    
+    // 同一秒内的多次备份在 ID 后面加序号区分
+    let mut id = format!("set_{}", timestamp);
+    let mut n = 1;
+    while dir.join(format!("{}.json", id)).exists() {
+        n += 1;
+        id = format!("set_{}_{}", timestamp, n);
+    }
+    
+    let set = SnapshotSet {
+        id: id.clone(),
+        group: group.map(str::to_string),
+        timestamp,
+        members,
+    };
+    
+    let path = dir.join(format!("{}.json", id));
+    let set_str = serde_json::to_string_pretty(&set).with_context(|| "序列化快照集失败")?;
+    crypto::write_file(config, &path, set_str.as_bytes())
+        .with_context(|| format!("写入快照集失败: {:?}", path))?;
+    
+    Ok(id)
+}
+
+// 列出所有快照集（最新的在前）
+pub fn list_sets(config: &Config) -> Result<Vec<SnapshotSet>> {
+    let dir = sets_dir(config);
+    if !dir.exists() {
+        return Ok(Vec::new());
+    }
+    
+    let entries = fs::read_dir(&dir).with_context(|| format!("读取快照集目录失败: {:?}", dir))?;
+    
+    let mut sets = Vec::new();
+    for entry in entries {
+        let path = entry?.path();
+        if path.extension().map(|e| e != "json").unwrap_or(true) {
+            continue;
+        }
+        
+        let set_str = crypto::read_to_string(config, &path)
+            .with_context(|| format!("读取快照集失败: {:?}", path))?;
+        let set: SnapshotSet = serde_json::from_str(&set_str)
+            .with_context(|| format!("解析快照集失败: {:?}", path))?;
+        sets.push(set);
+    }
+    
+    sets.sort_by(|a, b| b.timestamp.cmp(&a.timestamp).then_with(|| b.id.cmp(&a.id)));
+    
+    Ok(sets)
+}
+
+// 快照集中已经被删除的快照
+pub fn missing_members<'a>(config: &Config, set: &'a SnapshotSet) -> Vec<&'a SetMember> {
+    set.members
+        .iter()
+        .filter(|m| !config.backup_dir.join(&m.snapshot).exists())
+        .collect()
+}
+
+// 指定范围内最新的完整快照集
+pub fn latest_set(config: &Config, group: Option<&str>) -> Result<Option<SnapshotSet>> {
+    Ok(list_sets(config)?
+        .into_iter()
+        .filter(|set| set.group.as_deref() == group)
+        .find(|set| missing_members(config, set).is_empty()))
+}
+
+// 每个范围最新的快照集引用的快照名称，清理时需要保留以保证它们完整
+pub fn latest_set_snapshots(config: &Config) -> Result<HashSet<String>> {
+    let mut scopes = HashSet::new();
+    let mut names = HashSet::new();
+    
+    for set in list_sets(config)? {
+        if scopes.insert(set.group.clone()) {
+            names.extend(set.members.into_iter().map(|m| m.snapshot));
+        }
+    }
+    
+    Ok(names)
+}
+
+// 删除所有快照都已被清理的快照集记录
+pub fn remove_orphaned_sets(config: &Config) -> Result<()> {
+    for set in list_sets(config)? {
+        if missing_members(config, &set).len() == set.members.len() {
+            let path = sets_dir(config).join(format!("{}.json", set.id));
+            fs::remove_file(&path).with_context(|| format!("删除快照集失败: {:?}", path))?;
+        }
+    }
+    
+    Ok(())
+}
+
+fn format_time(timestamp: u64) -> String {
+    Local
+        .timestamp_opt(timestamp as i64, 0)
+        .single()
+        .map(|t| t.format("%Y-%m-%d %H:%M:%S").to_string())
+        .unwrap_or_else(|| "-".to_string())
+}
+
+// 选择一个快照集，把其中所有项目作为一个整体恢复
+pub fn restore_set(config: &Config) -> Result<()> {
+    let sets = list_sets(config)?;
+    if sets.is_empty() {
+        println!("没有快照集。组备份或一键备份时会自动创建快照集。");
+        return Ok(());
+    }
+    
+    println!("快照集 (最新的在前):");
+    for (i, set) in sets.iter().enumerate() {
+        let missing = missing_members(config, set).len();
+        let status = if missing > 0 { format!(" [不完整: 缺少 {} 个快照]", missing) } else { String::new() };
+        println!(
+            "  {}. {} {} {}，{} 个项目{}",
+            i + 1,
+            set.id,
+            format_time(set.timestamp),
+            set.scope(),
+            set.members.len(),
+            status
+        );
+    }
+    
+    print!("请选择要恢复的快照集 (或输入0取消): ");
+    let mut input = String::new();
+    std::io::stdin().read_line(&mut input)?;
+    
+    let set = match input.trim().parse::<usize>() {
+        Ok(num) if num > 0 && num <= sets.len() => &sets[num - 1],
+        Ok(0) => return Ok(()),
+        _ => {
+            println!("无效的选择。操作已取消。");
+            return Ok(());
+        }
+    };
+    
+    // 只恢复一部分会重新造成不一致，缺少快照或项目时整个快照集都不恢复
+    let missing = missing_members(config, set);
+    if !missing.is_empty() {
+        println!("快照集 {} 不完整，以下快照已被删除，无法作为一个整体恢复:", set.id);
+        for member in missing {
+            println!("  {}: {}", member.item, member.snapshot);
+        }
+        return Ok(());
+    }
+    
+    let mut targets: Vec<(&SyncItem, PathBuf)> = Vec::new();
+    for member in &set.members {
+        match config.sync_items.iter().find(|item| item.name == member.item) {
+            Some(item) => targets.push((item, config.backup_dir.join(&member.snapshot))),
+            None => {
+                println!("项目 {} 已不存在，无法恢复快照集 {}。", member.item, set.id);
+                return Ok(());
+            }
+        }
+    }
+    
+    println!("快照集 {} ({}):", set.id, set.scope());
+    for (item, backup) in &targets {
+        println!("  {} <- {}", item.name, file_name(backup));
+    }
+    
+    println!("要恢复快照集中的 {} 个项目吗？这将覆盖现有文件。", targets.len());
+    print!("请输入'yes'确认: ");
+    let mut input = String::new();
+    std::io::stdin().read_line(&mut input)?;
+    
+    if input.trim().to_lowercase() != "yes" {
+        println!("恢复操作已取消。");
+        return Ok(());
+    }
+    
+    // 任何一个快照被拒绝都取消整个快照集，不做部分恢复
+    for (_, backup) in &targets {
+        if !validate::confirm_restore(config, backup)? || !version::confirm_version(config, backup)? {
+            println!("恢复操作已取消。");
+            return Ok(());
+        }
+    }
+    
+    // 客户端在运行时恢复没有意义，它退出时会覆盖恢复的文件
+    if !process::ensure_game_closed(config)? {
+        println!("恢复操作已取消。");
+        return Ok(());
+    }
+    
+    let preview: Vec<(&str, &Path)> = targets.iter().map(|(item, _)| (item.name.as_str(), item.source_path.as_path())).collect();
+    if !remap::confirm_targets(config, &preview)? {
+        println!("恢复操作已取消。");
+        return Ok(());
+    }
+    
+    let mut session = RestoreSession::begin(config);
+    
+    for (item, backup) in &targets {
+        // 按路径映射表写入本机的对应位置
+        let item = &remap::remap_item(config, item);
+        restore_item(&mut session, item, backup)?;
+        println!("已恢复 {}: {:?} <- {:?}", item.name, item.source_path, backup);
+    }
+    
+    println!("快照集 {} 恢复完成！", set.id);
+    println!("恢复前的内容已保存为安全快照，可在恢复选项中撤销上次恢复。");
+    
+    Ok(())
+}
+
+fn file_name(path: &Path) -> String {
+    path.file_name()
+        .map(|n| n.to_string_lossy().to_string())
+        .unwrap_or_default()
+}
\ No newline at end of file
@@ -0,0 +1,574 @@
+use anyhow::{Context, Result};
+use serde::Serialize;
+use serde_json::Value;
+use std::path::Path;
+
+// 可以按键解析的设置文件格式
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Format {
+    // game.cfg、input.ini 等
+    Ini,
+    // PersistedSettings.json 等
+    Json,
+    Text,
+}
+
+pub fn format_of(path: &Path) -> Format {
+    let ext = path
+        .extension()
+        .map(|e| e.to_string_lossy().to_lowercase())
+        .unwrap_or_default();
+    
+    match ext.as_str() {
+        "ini" | "cfg" => Format::Ini,
+        "json" => Format::Json,
+        _ => Format::Text,
+    }
+}
+
+// 去掉 Windows 编辑器常加的 BOM
+pub fn strip_bom(text: &str) -> &str {
+    text.strip_prefix('\u{feff}').unwrap_or(text)
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct IniSection {
+    // 第一个节之前的键属于名称为空的节
+    pub name: String,
+    pub entries: Vec<(String, String)>,
+}
+
+// 解析 INI 文件；注释和空行被忽略，同一节出现多次时合并
+pub fn parse_ini(text: &str) -> Vec<IniSection> {
+    let mut sections = vec![IniSection::default()];
+    let mut current = 0;
+    
+    for line in strip_bom(text).lines() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with(';') || line.starts_with('#') {
+            continue;
+        }
+        
+        if let Some(name) = line.strip_prefix('[').and_then(|l| l.strip_suffix(']')) {
+            let name = name.trim();
+            current = match sections.iter().position(|s| s.name == name) {
+                Some(idx) => idx,
+                None => {
+                    sections.push(IniSection {
+                        name: name.to_string(),
+                        entries: Vec::new(),
+                    });
+                    sections.len() - 1
+                },
+            };
+            continue;
+        }
+        
+        let (key, value) = match line.split_once('=') {
+            Some((key, value)) => (key.trim(), value.trim()),
+            None => (line, ""),
+        };
+        
+        let entries = &mut sections[current].entries;
+        match entries.iter_mut().find(|(k, _)| k == key) {
+            Some(entry) => entry.1 = value.to_string(),
+            None => entries.push((key.to_string(), value.to_string())),
+        }
+    }
+    
+    if sections[0].entries.is_empty() {
+        sections.remove(0);
+    }
+    
+    sections
+}
+
+// INI 中一个键的显示名称
+pub fn ini_key(section: &str, key: &str) -> String {
+    if section.is_empty() {
+        key.to_string()
+    } else {
+        format!("[{}] {}", section, key)
+    }
+}
+
+// 把 JSON 展开成 "路径 = 值" 的列表；元素是带 name 字段的对象时用名称代替下标，
+// 这样 PersistedSettings.json 中的设置项插入或重排后路径保持不变
+pub fn flatten_json(value: &Value) -> Vec<(String, String)> {
+    let mut entries = Vec::new();
+    flatten_value(value, String::new(), &mut entries);
+    entries
+}
+
+fn flatten_value(value: &Value, path: String, entries: &mut Vec<(String, String)>) {
+    match value {
+        Value::Object(map) if !map.is_empty() => {
+            for (key, child) in map {
+                let child_path = if path.is_empty() {
+                    key.clone()
+                } else {
+                    format!("{}.{}", path, key)
+                };
+                flatten_value(child, child_path, entries);
+            }
+        },
+        Value::Array(items) if !items.is_empty() => {
+            for (i, child) in items.iter().enumerate() {
+                flatten_value(child, format!("{}[{}]", path, element_label(child, i)), entries);
+            }
+        },
+        _ => entries.push((path, value.to_string())),
+    }
+}
+
+// 数组元素在路径中的标签：带 name 字段的对象用名称，否则用下标
+pub fn element_label(value: &Value, index: usize) -> String {
+    match value.get("name").and_then(Value::as_str) {
+        Some(name) => name.to_string(),
+        None => index.to_string(),
+    }
+}
+
+// 按格式把设置文件展开成有序的 "键 = 值" 列表；Text 格式没有键，返回 None
+pub fn flatten(format: Format, text: &str) -> Result<Option<Vec<(String, String)>>> {
+    match format {
+        Format::Ini => Ok(Some(
+            parse_ini(text)
+                .iter()
+                .flat_map(|section| {
+                    section
+                        .entries
+                        .iter()
+                        .map(|(key, value)| (ini_key(&section.name, key), value.clone()))
+                })
+                .collect(),
+        )),
+        Format::Json => {
+            let value: Value = serde_json::from_str(strip_bom(text))
+                .with_context(|| "解析 JSON 失败")?;
+            Ok(Some(flatten_json(&value)))
+        },
+        Format::Text => Ok(None),
+    }
+}
+
+// 要写入 INI 文件的内容：节名、键名和新值（None 表示删除该键）
+pub struct IniChange {
+    pub section: String,
+    pub key: String,
+    pub value: Option<String>,
+}
+
+fn is_comment(line: &str) -> bool {
+    line.starts_with(';') || line.starts_with('#')
+}
+
+// 把当前节缺少的键插入到 insert_at 之后
+fn insert_missing_keys(
+    out: &mut Vec<String>,
+    section: &str,
+    insert_at: usize,
+    changes: &[IniChange],
+    written: &mut [bool],
+) {
+    let mut pos = insert_at;
+    for (i, change) in changes.iter().enumerate() {
+        if written[i] || change.section != section {
+            continue;
+        }
+        written[i] = true;
+        if let Some(value) = &change.value {
+            out.insert(pos, format!("{}={}", change.key, value));
+            pos += 1;
+        }
+    }
+}
+
+// 把 changes 写入 INI 文本；只改动涉及的行，其余内容（顺序、注释、空行、换行符）保持原样。
+// 已有的键原地修改，缺少的键追加到所在节的最后一个键之后，缺少的节追加到文件末尾
+pub fn merge_ini(text: &str, changes: &[IniChange]) -> String {
+    let bom = if text.starts_with('\u{feff}') { "\u{feff}" } else { "" };
+    let text = strip_bom(text);
+    let newline = if text.contains("\r\n") { "\r\n" } else { "\n" };
+    
+    let mut out: Vec<String> = Vec::new();
+    let mut written = vec![false; changes.len()];
+    let mut section = String::new();
+    // 当前节中最后一个键（或节标题）在 out 中的位置，缺少的键插在它后面
+    let mut insert_at: usize = 0;
+    
+    for line in text.lines() {
+        let trimmed = line.trim();
+        
+        if let Some(name) = trimmed.strip_prefix('[').and_then(|l| l.strip_suffix(']')) {
+            insert_missing_keys(&mut out, &section, insert_at, changes, &mut written);
+            section = name.trim().to_string();
+            out.push(line.to_string());
+            insert_at = out.len();
+            continue;
+        }
+        
+        if trimmed.is_empty() || is_comment(trimmed) {
+            out.push(line.to_string());
+            continue;
+        }
+        
+        let key = match trimmed.split_once('=') {
+            Some((key, _)) => key.trim(),
+            None => trimmed,
+        };
+        
+        let change = changes
+            .iter()
+            .position(|c| c.section == section && c.key == key);
+        
+        match change {
+            Some(i) => {
+                written[i] = true;
+                if let Some(value) = &changes[i].value {
+                    // 保留原来的键名写法和等号前后的空白
+                    let prefix = match line.find('=') {
+                        Some(eq) => {
+                            let after = &line[eq + 1..];
+                            let spaces = after.len() - after.trim_start().len();
+                            line[..eq + 1 + spaces].to_string()
+                        },
+                        None => format!("{}=", line),
+                    };
+                    out.push(format!("{}{}", prefix, value));
+                    insert_at = out.len();
+                }
+            },
+            None => {
+                out.push(line.to_string());
+                insert_at = out.len();
+            },
+        }
+    }
+    
+    insert_missing_keys(&mut out, &section, insert_at, changes, &mut written);
+    
+    // 文件中没有的节
+    let mut missing: Vec<&str> = Vec::new();
+    for (i, change) in changes.iter().enumerate() {
+        if !written[i] && change.value.is_some() && !missing.contains(&change.section.as_str()) {
+            missing.push(&change.section);
+        }
+    }
+    for name in missing {
+        if out.last().is_some_and(|l| !l.trim().is_empty()) {
+            out.push(String::new());
+        }
+        if !name.is_empty() {
+            out.push(format!("[{}]", name));
+        }
+        for change in changes.iter().filter(|c| c.section == name) {
+            if let Some(value) = &change.value {
+                out.push(format!("{}={}", change.key, value));
+            }
+        }
+    }
+    
+    let mut merged = out.join(newline);
+    if text.ends_with('\n') || text.is_empty() {
+        merged.push_str(newline);
+    }
+    
+    format!("{}{}", bom, merged)
+}
+
+// JSON 路径中的一段：对象的键，或数组元素（按 name 字段或下标匹配）
+#[derive(Debug, Clone, PartialEq)]
+enum Segment {
+    Key(String),
+    Element(String),
+}
+
+// 解析 flatten_json 使用的路径写法，例如 "files[Game.cfg].sections[HUD].settings[GlobalScale]"
+fn parse_json_path(path: &str) -> Result<Vec<Segment>> {
+    let mut segments = Vec::new();
+    let mut rest = path.trim();
+    
+    while !rest.is_empty() {
+        if let Some(after) = rest.strip_prefix('[') {
+            let end = after
+                .find(']')
+                .ok_or_else(|| anyhow::anyhow!("JSON 路径中的 '[' 没有闭合: {}", path))?;
+            segments.push(Segment::Element(after[..end].to_string()));
+            rest = &after[end + 1..];
+        } else {
+            let rest_key = rest.strip_prefix('.').unwrap_or(rest);
+            let end = rest_key.find(['.', '[']).unwrap_or(rest_key.len());
+            if end == 0 {
+                return Err(anyhow::anyhow!("无效的 JSON 路径: {}", path));
+            }
+            segments.push(Segment::Key(rest_key[..end].to_string()));
+            rest = &rest_key[end..];
+        }
+    }
+    
+    if segments.is_empty() {
+        return Err(anyhow::anyhow!("JSON 路径为空"));
+    }
+    
+    Ok(segments)
+}
+
+fn element_index(items: &[Value], label: &str) -> Option<usize> {
+    items
+        .iter()
+        .enumerate()
+        .position(|(i, item)| element_label(item, i) == label)
+}
+
+fn json_get<'a>(value: &'a Value, segments: &[Segment]) -> Option<&'a Value> {
+    segments.iter().try_fold(value, |current, segment| match segment {
+        Segment::Key(key) => current.get(key),
+        Segment::Element(label) => {
+            let items = current.as_array()?;
+            items.get(element_index(items, label)?)
+        },
+    })
+}
+
+// 把 new 写到 live 中的路径上（None 表示删除）；中间缺少的对象会被创建，
+// 缺少的命名数组元素会以 {"name": ...} 的形式追加
+fn json_set(live: &mut Value, segments: &[Segment], new: Option<Value>, path: &str) -> Result<()> {
+    let (last, parents) = segments.split_last().expect("路径不为空");
+    let mut current = live;
+    
+    for (i, segment) in parents.iter().enumerate() {
+        let next_is_element = matches!(segments[i + 1], Segment::Element(_));
+        
+        current = match segment {
+            Segment::Key(key) => {
+                let map = current
+                    .as_object_mut()
+                    .ok_or_else(|| anyhow::anyhow!("{} 中 '{}' 的上一级不是对象", path, key))?;
+                if !map.contains_key(key) {
+                    if new.is_none() {
+                        return Ok(());
+                    }
+                    let empty = if next_is_element { Value::Array(Vec::new()) } else { Value::Object(Default::default()) };
+                    map.insert(key.clone(), empty);
+                }
+                map.get_mut(key).expect("刚刚插入")
+            },
+            Segment::Element(label) => {
+                let items = current
+                    .as_array_mut()
+                    .ok_or_else(|| anyhow::anyhow!("{} 中 '[{}]' 的上一级不是数组", path, label))?;
+                let idx = match element_index(items, label) {
+                    Some(idx) => idx,
+                    None if new.is_none() => return Ok(()),
+                    None if label.parse::<usize>().is_ok() => {
+                        return Err(anyhow::anyhow!("当前文件中不存在 {} 的第 {} 个元素", path, label));
+                    },
+                    None => {
+                        items.push(serde_json::json!({ "name": label }));
+                        items.len() - 1
+                    },
+                };
+                &mut items[idx]
+            },
+        };
+    }
+    
+    match last {
+        Segment::Key(key) => {
+            let map = current
+                .as_object_mut()
+                .ok_or_else(|| anyhow::anyhow!("{} 中 '{}' 的上一级不是对象", path, key))?;
+            match new {
+                Some(value) => {
+                    map.insert(key.clone(), value);
+                },
+                None => {
+                    map.shift_remove(key);
+                },
+            }
+        },
+        Segment::Element(label) => {
+            let items = current
+                .as_array_mut()
+                .ok_or_else(|| anyhow::anyhow!("{} 中 '[{}]' 的上一级不是数组", path, label))?;
+            match (element_index(items, label), new) {
+                (Some(idx), Some(value)) => items[idx] = value,
+                (Some(idx), None) => {
+                    items.remove(idx);
+                },
+                (None, Some(value)) => items.push(value),
+                (None, None) => {},
+            }
+        },
+    }
+    
+    Ok(())
+}
+
+// 解析 JSON 文本，无效时拒绝
+pub fn parse_json(text: &str, what: &str) -> Result<Value> {
+    serde_json::from_str(strip_bom(text)).with_context(|| format!("{}不是有效的 JSON", what))
+}
+
+// 检测文件使用的缩进，找不到时使用两个空格
+fn detect_indent(text: &str) -> String {
+    text.lines()
+        .skip(1)
+        .map(|line| &line[..line.len() - line.trim_start().len()])
+        .find(|indent| !indent.is_empty())
+        .unwrap_or("  ")
+        .to_string()
+}
+
+// 把快照中 paths 指向的值写入当前的 JSON 文本，其余键保持不变；快照中没有的路径会从当前文件删除。
+// 输出沿用当前文件的缩进、换行符和 BOM
+pub fn merge_json(live_text: &str, snapshot_text: &str, paths: &[String]) -> Result<String> {
+    let mut live = parse_json(live_text, "当前文件")?;
+    let snapshot = parse_json(snapshot_text, "快照中的文件")?;
+    
+    for path in paths {
+        let segments = parse_json_path(path)?;
+        let value = json_get(&snapshot, &segments).cloned();
+        json_set(&mut live, &segments, value, path)?;
+    }
+    
+    let bom = if live_text.starts_with('\u{feff}') { "\u{feff}" } else { "" };
+    let indent = detect_indent(strip_bom(live_text));
+    
+    let mut buffer = Vec::new();
+    let formatter = serde_json::ser::PrettyFormatter::with_indent(indent.as_bytes());
+    let mut serializer = serde_json::Serializer::with_formatter(&mut buffer, formatter);
+    live.serialize(&mut serializer).with_context(|| "序列化 JSON 失败")?;
+    
+    let mut merged = String::from_utf8(buffer).with_context(|| "序列化 JSON 失败")?;
+    if live_text.contains("\r\n") {
+        merged = merged.replace('\n', "\r\n");
+    }
+    if live_text.ends_with('\n') {
+        merged.push_str(if live_text.contains("\r\n") { "\r\n" } else { "\n" });
+    }
+    
+    Ok(format!("{}{}", bom, merged))
+}
+
+// 列出 JSON 中可以整体恢复的设置块：本身包含数组或对象的命名数组元素，
+// 例如 PersistedSettings.json 的 files[Game.cfg] 和 files[Game.cfg].sections[HUD]
+pub fn json_blocks(value: &Value) -> Vec<String> {
+    let mut blocks = Vec::new();
+    collect_blocks(value, String::new(), &mut blocks);
+    blocks
+}
+
+fn collect_blocks(value: &Value, path: String, blocks: &mut Vec<String>) {
+    match value {
+        Value::Object(map) => {
+            for (key, child) in map {
+                let child_path = if path.is_empty() { key.clone() } else { format!("{}.{}", path, key) };
+                collect_blocks(child, child_path, blocks);
+            }
+        },
+        Value::Array(items) => {
+            for (i, child) in items.iter().enumerate() {
+                let has_children = child
+                    .as_object()
+                    .map(|map| map.values().any(|v| v.is_array() || v.is_object()))
+                    .unwrap_or(false);
+                if !has_children {
+                    continue;
+                }
+                
+                let child_path = format!("{}[{}]", path, element_label(child, i));
+                blocks.push(child_path.clone());
+                collect_blocks(child, child_path, blocks);
+            }
+        },
+        _ => {},
+    }
+}
+
+// 读取 INI 文本中某个键的值
+pub fn ini_value(text: &str, section: &str, key: &str) -> Option<String> {
+    parse_ini(text)
+        .into_iter()
+        .find(|s| s.name == section)?
+        .entries
+        .into_iter()
+        .find(|(k, _)| k == key)
+        .map(|(_, v)| v)
+}
+
+// 读取 JSON 文本中某个路径的值（可以是对象或数组）
+pub fn json_value(text: &str, path: &str) -> Result<Option<Value>> {
+    let value = parse_json(text, "文件")?;
+    let segments = parse_json_path(path)?;
+    Ok(json_get(&value, &segments).cloned())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    fn change(section: &str, key: &str, value: Option<&str>) -> IniChange {
+        IniChange {
+            section: section.to_string(),
+            key: key.to_string(),
+            value: value.map(str::to_string),
+        }
+    }
+    
+    #[test]
+    fn merge_ini_changes_only_selected_keys() {
+        let live = "; 注释\r\n[General]\r\nWindowMode = 1\r\nvsync=0\r\n\r\n[HUD]\r\nScale=50\r\n";
+        let changes = vec![
+            change("General", "WindowMode", Some("2")),
+            change("General", "Width", Some("1920")),
+            change("General", "vsync", None),
+            change("Sound", "Volume", Some("80")),
+        ];
+        
+        let merged = merge_ini(live, &changes);
+        
+        assert_eq!(
+            merged,
+            "; 注释\r\n[General]\r\nWindowMode = 2\r\nWidth=1920\r\n\r\n[HUD]\r\nScale=50\r\n\r\n[Sound]\r\nVolume=80\r\n"
+        );
+    }
+    
+    #[test]
+    fn merge_ini_keeps_bom_and_missing_trailing_newline() {
+        let merged = merge_ini("\u{feff}[HUD]\nScale=50", &[change("HUD", "Scale", Some("75"))]);
+        
+        assert_eq!(merged, "\u{feff}[HUD]\nScale=75");
+    }
+    
+    #[test]
+    fn merge_json_replaces_selected_paths_only() {
+        let live = "{\n    \"files\": [\n        {\n            \"name\": \"Game.cfg\",\n            \"value\": 1\n        }\n    ],\n    \"other\": true,\n    \"old\": 1\n}\n";
+        let snapshot = r#"{"files": [{"name": "Game.cfg", "value": 2}], "other": false}"#;
+        let paths = vec!["files[Game.cfg].value".to_string(), "old".to_string()];
+        
+        let merged = merge_json(live, snapshot, &paths).unwrap();
+        
+        assert_eq!(
+            merged,
+            "{\n    \"files\": [\n        {\n            \"name\": \"Game.cfg\",\n            \"value\": 2\n        }\n    ],\n    \"other\": true\n}\n"
+        );
+    }
+    
+    #[test]
+    fn merge_json_adds_missing_named_elements() {
+        let snapshot = r#"{"files": [{"name": "Input.ini", "sections": []}]}"#;
+        
+        let merged = merge_json("{}", snapshot, &["files[Input.ini]".to_string()]).unwrap();
+        let value: Value = serde_json::from_str(&merged).unwrap();
+        
+        assert_eq!(value, serde_json::json!({ "files": [{ "name": "Input.ini", "sections": [] }] }));
+    }
+    
+    #[test]
+    fn merge_json_rejects_invalid_input() {
+        assert!(merge_json("{", "{}", &["a".to_string()]).is_err());
+        assert!(merge_json("{}", "not json", &["a".to_string()]).is_err());
+    }
+}
\ No newline at end of file
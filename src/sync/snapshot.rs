@@ -0,0 +1,498 @@
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeSet;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::config::Config;
+use crate::sync::archive;
+use crate::sync::crypto;
+use crate::sync::index::FileEntry;
+use crate::sync::restore::remove_path;
+use crate::sync::version;
+
+// 游戏配置和脚本备份的名称前缀
+pub const GAME_CONFIG_SERIES: &str = "game_config";
+pub const SCRIPTS_SERIES: &str = "scripts";
+
+// 快照的附加信息（固定状态等）存放在备份目录下的该子目录中
+const META_DIR_NAME: &str = "snapshot_meta";
+
+// 备份目录中一个 "{series}_{timestamp}" 形式的快照
+#[derive(Debug, Clone)]
+pub struct Snapshot {
+    pub timestamp: u64,
+    pub path: PathBuf,
+}
+
+impl Snapshot {
+    pub fn file_name(&self) -> String {
+        self.path
+            .file_name()
+            .map(|n| n.to_string_lossy().to_string())
+            .unwrap_or_default()
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct SnapshotMeta {
+    #[serde(default)]
+    pub pinned: bool,
+    // 备份时记录的文件清单，路径相对于快照内容的根目录；旧快照没有清单
+    #[serde(default)]
+    pub manifest: Option<Vec<FileEntry>>,
+    // 备份前校验发现的问题，非空时恢复需要强制确认
+    #[serde(default)]
+    pub suspect: Vec<String>,
+    // 创建快照时安装的游戏版本；检测不到或旧快照时为空
+    #[serde(default)]
+    pub game_version: Option<String>,
+    #[serde(default)]
+    pub note: Option<String>,
+    #[serde(default)]
+    pub tags: Vec<String>,
+}
+
+// 备份时或之后为快照添加的备注和标签
+#[derive(Debug, Clone, Default)]
+pub struct SnapshotLabel {
+    pub note: Option<String>,
+    pub tags: Vec<String>,
+}
+
+impl SnapshotLabel {
+    pub fn is_empty(&self) -> bool {
+        self.note.is_none() && self.tags.is_empty()
+    }
+}
+
+// 解析快照名称中的时间戳，前缀之后（去掉压缩包扩展名）必须全是数字
+pub fn parse_timestamp(file_name: &str, series: &str) -> Option<u64> {
+    let ts = archive::strip_extension(file_name)
+        .strip_prefix(series)?
+        .strip_prefix('_')?;
+    
+    if ts.is_empty() || !ts.chars().all(|c| c.is_ascii_digit()) {
+        return None;
+    }
+    
+    ts.parse().ok()
+}
+
+// 列出某个系列的所有快照（最新的在前）
+pub fn list_snapshots(config: &Config, series: &str) -> Result<Vec<Snapshot>> {
+    if !config.backup_dir.exists() {
+        return Ok(Vec::new());
+    }
+    
+    let entries = fs::read_dir(&config.backup_dir)
+        .with_context(|| format!("读取备份目录失败: {:?}", config.backup_dir))?;
+    
+    let mut snapshots = Vec::new();
+    
+    for entry in entries {
+        let entry = entry?;
+        let path = entry.path();
+        let file_name = entry.file_name().to_string_lossy().to_string();
+        
+        if let Some(timestamp) = parse_timestamp(&file_name, series) {
+            snapshots.push(Snapshot {
+                timestamp,
+                path,
+            });
+        }
+    }
+    
+    snapshots.sort_by_key(|s| std::cmp::Reverse(s.timestamp));
+    
+    Ok(snapshots)
+}
+
+// 所有受管理的快照系列：同步项目、游戏配置和脚本
+pub fn all_series(config: &Config) -> Vec<String> {
+    let mut series: Vec<String> = config.sync_items.iter().map(|item| item.name.clone()).collect();
+    series.push(GAME_CONFIG_SERIES.to_string());
+    series.push(SCRIPTS_SERIES.to_string());
+    series
+}
+
+fn meta_path(config: &Config, snapshot_path: &Path) -> PathBuf {
+    let file_name = snapshot_path.file_name().unwrap_or_default().to_string_lossy();
+    config
+        .backup_dir
+        .join(META_DIR_NAME)
+        .join(format!("{}.json", file_name))
+}
+
+pub fn load_meta(config: &Config, snapshot: &Snapshot) -> Result<SnapshotMeta> {
+    read_meta(config, &snapshot.path)
+}
+
+pub fn save_meta(config: &Config, snapshot: &Snapshot, meta: &SnapshotMeta) -> Result<()> {
+    write_meta(config, &snapshot.path, meta)
+}
+
+fn read_meta(config: &Config, snapshot_path: &Path) -> Result<SnapshotMeta> {
+    let path = meta_path(config, snapshot_path);
+    if !path.exists() {
+        return Ok(SnapshotMeta::default());
+    }
+    
+    let meta_str = crypto::read_to_string(config, &path)
+        .with_context(|| format!("读取快照信息失败: {:?}", path))?;
+    let meta = serde_json::from_str(&meta_str)
+        .with_context(|| format!("解析快照信息失败: {:?}", path))?;
+    
+    Ok(meta)
+}
+
+fn write_meta(config: &Config, snapshot_path: &Path, meta: &SnapshotMeta) -> Result<()> {
+    let path = meta_path(config, snapshot_path);
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)
+            .with_context(|| format!("创建快照信息目录失败: {:?}", parent))?;
+    }
+    
+    let meta_str = serde_json::to_string_pretty(meta)
+        .with_context(|| "序列化快照信息失败")?;
+    crypto::write_file(config, &path, meta_str.as_bytes())
+        .with_context(|| format!("写入快照信息失败: {:?}", path))?;
+    
+    Ok(())
+}
+
+// 为刚创建的快照记录文件清单，供以后校验
+pub fn save_manifest(config: &Config, snapshot_path: &Path, manifest: Vec<FileEntry>) -> Result<()> {
+    let mut meta = read_meta(config, snapshot_path)?;
+    meta.manifest = Some(manifest);
+    write_meta(config, snapshot_path, &meta)
+}
+
+// 把快照标记为可疑，记录备份前校验发现的问题
+pub fn mark_suspect(config: &Config, snapshot_path: &Path, reasons: Vec<String>) -> Result<()> {
+    let mut meta = read_meta(config, snapshot_path)?;
+    meta.suspect = reasons;
+    write_meta(config, snapshot_path, &meta)
+}
+
+pub fn suspect_reasons(config: &Config, snapshot_path: &Path) -> Result<Vec<String>> {
+    Ok(read_meta(config, snapshot_path)?.suspect)
+}
+
+pub fn save_game_version(config: &Config, snapshot_path: &Path, version: String) -> Result<()> {
+    let mut meta = read_meta(config, snapshot_path)?;
+    meta.game_version = Some(version);
+    write_meta(config, snapshot_path, &meta)
+}
+
+pub fn game_version(config: &Config, snapshot_path: &Path) -> Result<Option<String>> {
+    Ok(read_meta(config, snapshot_path)?.game_version)
+}
+
+// 为快照设置备注并加上标签，已有的标签保留
+pub fn apply_label(config: &Config, snapshot_path: &Path, label: &SnapshotLabel) -> Result<()> {
+    let mut meta = read_meta(config, snapshot_path)?;
+    if label.note.is_some() {
+        meta.note = label.note.clone();
+    }
+    for tag in &label.tags {
+        if !meta.tags.contains(tag) {
+            meta.tags.push(tag.clone());
+        }
+    }
+    write_meta(config, snapshot_path, &meta)
+}
+
+// 解析用逗号分隔的标签，去掉空白和重复的标签
+pub fn parse_tags(input: &str) -> Vec<String> {
+    let mut tags: Vec<String> = Vec::new();
+    for tag in input.split([',', '，']).map(str::trim).filter(|t| !t.is_empty()) {
+        if !tags.iter().any(|t| t == tag) {
+            tags.push(tag.to_string());
+        }
+    }
+    tags
+}
+
+// 询问本次备份的备注和标签，都可以直接回车跳过
+pub fn ask_label() -> Result<SnapshotLabel> {
+    print!("请输入快照备注 (直接回车跳过): ");
+    let mut input = String::new();
+    std::io::stdin().read_line(&mut input)?;
+    let note = Some(input.trim().to_string()).filter(|n| !n.is_empty());
+    
+    print!("请输入标签，多个标签用逗号分隔 (直接回车跳过): ");
+    let mut input = String::new();
+    std::io::stdin().read_line(&mut input)?;
+    
+    Ok(SnapshotLabel {
+        note,
+        tags: parse_tags(&input),
+    })
+}
+
+// 快照列表中显示的附加信息：游戏版本、固定、可疑、标签和备注
+fn describe_meta(meta: &SnapshotMeta) -> String {
+    let mut text = String::new();
+    if meta.pinned {
+        text.push_str(" [已固定]");
+    }
+    if !meta.suspect.is_empty() {
+        text.push_str(" [可疑]");
+    }
+    if !meta.tags.is_empty() {
+        text.push_str(&format!(" [标签: {}]", meta.tags.join(", ")));
+    }
+    if let Some(note) = &meta.note {
+        text.push_str(&format!(" 备注: {}", note));
+    }
+    text
+}
+
+pub fn describe(config: &Config, snapshot_path: &Path) -> String {
+    let labels = read_meta(config, snapshot_path)
+        .map(|meta| describe_meta(&meta))
+        .unwrap_or_default();
+    format!("{}{}", version::version_label(config, snapshot_path), labels)
+}
+
+// 快照带有标签时让用户按标签筛选，直接回车显示全部
+pub fn filter_by_tag(config: &Config, snapshots: Vec<Snapshot>) -> Result<Vec<Snapshot>> {
+    let mut tagged = Vec::new();
+    let mut all_tags = BTreeSet::new();
+    for snapshot in snapshots {
+        let tags = read_meta(config, &snapshot.path)?.tags;
+        all_tags.extend(tags.iter().cloned());
+        tagged.push((snapshot, tags));
+    }
+    
+    if all_tags.is_empty() {
+        return Ok(tagged.into_iter().map(|(snapshot, _)| snapshot).collect());
+    }
+    
+    println!("已有的标签: {}", all_tags.into_iter().collect::<Vec<_>>().join(", "));
+    print!("按标签筛选快照 (直接回车显示全部): ");
+    let mut input = String::new();
+    std::io::stdin().read_line(&mut input)?;
+    
+    let tag = input.trim();
+    let filtered: Vec<Snapshot> = tagged
+        .into_iter()
+        .filter(|(_, tags)| tag.is_empty() || tags.iter().any(|t| t.eq_ignore_ascii_case(tag)))
+        .map(|(snapshot, _)| snapshot)
+        .collect();
+    
+    if filtered.is_empty() {
+        println!("没有带有标签 '{}' 的快照。", tag);
+    }
+    
+    Ok(filtered)
+}
+
+// 系统临时目录中的一个工作目录，离开作用域时自动删除
+pub struct ScratchDir {
+    path: PathBuf,
+}
+
+impl ScratchDir {
+    pub fn new(label: &str) -> Self {
+        let nanos = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_nanos();
+        
+        ScratchDir {
+            path: std::env::temp_dir().join(format!("game_tool_{}_{}_{}", label, std::process::id(), nanos)),
+        }
+    }
+    
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+}
+
+impl Drop for ScratchDir {
+    fn drop(&mut self) {
+        let _ = remove_path(&self.path);
+    }
+}
+
+// 以目录形式读取快照内容；压缩包会解压到临时目录，用完后自动删除
+pub struct SnapshotContent {
+    path: PathBuf,
+    _scratch: Option<ScratchDir>,
+}
+
+impl SnapshotContent {
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+}
+
+// 打开快照（目录或压缩包）以便读取其中的文件；已加密的快照先解密到临时目录
+pub fn open_snapshot(config: &Config, path: &Path) -> Result<SnapshotContent> {
+    if archive::format_of(path).is_none() {
+        if !crypto::tree_is_encrypted(path) {
+            return Ok(SnapshotContent {
+                path: path.to_path_buf(),
+                _scratch: None,
+            });
+        }
+        
+        let scratch = ScratchDir::new("decrypt");
+        crypto::decrypt_copy(config, path, scratch.path())?;
+        
+        return Ok(SnapshotContent {
+            path: scratch.path().to_path_buf(),
+            _scratch: Some(scratch),
+        });
+    }
+    
+    let scratch = ScratchDir::new("extract");
+    
+    if crypto::file_is_encrypted(path) {
+        // 解密后的压缩包放在临时目录内，保留原来的扩展名以便识别格式
+        let decrypted = scratch.path().join(path.file_name().unwrap_or_default());
+        let contents = scratch.path().join("contents");
+        fs::create_dir_all(scratch.path())
+            .with_context(|| format!("创建临时目录失败: {:?}", scratch.path()))?;
+        crypto::decrypt_copy(config, path, &decrypted)?;
+        archive::extract_archive(&decrypted, &contents)?;
+        remove_path(&decrypted)?;
+        
+        return Ok(SnapshotContent {
+            path: contents,
+            _scratch: Some(scratch),
+        });
+    }
+    
+    archive::extract_archive(path, scratch.path())?;
+    
+    Ok(SnapshotContent {
+        path: scratch.path().to_path_buf(),
+        _scratch: Some(scratch),
+    })
+}
+
+// 删除快照及其附加信息
+pub fn remove_snapshot(config: &Config, snapshot: &Snapshot) -> Result<()> {
+    remove_path(&snapshot.path)?;
+    remove_path(&meta_path(config, &snapshot.path))?;
+    Ok(())
+}
+
+// 计算文件或目录占用的总字节数
+pub fn path_size(path: &Path) -> u64 {
+    if path.is_dir() {
+        fs::read_dir(path)
+            .map(|entries| {
+                entries
+                    .filter_map(Result::ok)
+                    .map(|e| path_size(&e.path()))
+                    .sum()
+            })
+            .unwrap_or(0)
+    } else {
+        fs::metadata(path).map(|m| m.len()).unwrap_or(0)
+    }
+}
+
+// 让用户从所有系列中选择一个快照
+pub fn select_snapshot(config: &Config) -> Result<Option<Snapshot>> {
+    let series = all_series(config);
+    
+    println!("快照系列:");
+    for (i, name) in series.iter().enumerate() {
+        println!("  {}. {}", i + 1, name);
+    }
+    
+    print!("请选择系列 (或输入0取消): ");
+    let mut input = String::new();
+    std::io::stdin().read_line(&mut input)?;
+    
+    let series_idx: usize = match input.trim().parse() {
+        Ok(num) if num > 0 && num <= series.len() => num - 1,
+        Ok(0) => return Ok(None),
+        _ => {
+            println!("无效的选择。操作已取消。");
+            return Ok(None);
+        }
+    };
+    
+    let snapshots = filter_by_tag(config, list_snapshots(config, &series[series_idx])?)?;
+    if snapshots.is_empty() {
+        println!("{} 没有任何快照。", series[series_idx]);
+        return Ok(None);
+    }
+    
+    println!("{} 的快照:", series[series_idx]);
+    for (i, snapshot) in snapshots.iter().enumerate() {
+        println!("  {}. {}{}", i + 1, snapshot.file_name(), describe(config, &snapshot.path));
+    }
+    
+    print!("请选择快照 (或输入0取消): ");
+    let mut input = String::new();
+    std::io::stdin().read_line(&mut input)?;
+    
+    match input.trim().parse::<usize>() {
+        Ok(num) if num > 0 && num <= snapshots.len() => Ok(Some(snapshots[num - 1].clone())),
+        Ok(0) => Ok(None),
+        _ => {
+            println!("无效的选择。操作已取消。");
+            Ok(None)
+        }
+    }
+}
+
+// 固定或取消固定快照，固定的快照不会被清理
+pub fn toggle_pin_snapshot(config: &Config) -> Result<()> {
+    let snapshot = match select_snapshot(config)? {
+        Some(snapshot) => snapshot,
+        None => return Ok(()),
+    };
+    
+    let mut meta = load_meta(config, &snapshot)?;
+    meta.pinned = !meta.pinned;
+    save_meta(config, &snapshot, &meta)?;
+    
+    let status = if meta.pinned { "已固定" } else { "已取消固定" };
+    println!("快照 {} {}", snapshot.file_name(), status);
+    
+    Ok(())
+}
+
+// 编辑快照的备注和标签
+pub fn edit_snapshot_label(config: &Config) -> Result<()> {
+    let snapshot = match select_snapshot(config)? {
+        Some(snapshot) => snapshot,
+        None => return Ok(()),
+    };
+    
+    let mut meta = load_meta(config, &snapshot)?;
+    println!("当前备注: {}", meta.note.as_deref().unwrap_or("(无)"));
+    println!("当前标签: {}", if meta.tags.is_empty() { "(无)".to_string() } else { meta.tags.join(", ") });
+    
+    print!("请输入新的备注 (直接回车保留，输入 - 清除): ");
+    let mut input = String::new();
+    std::io::stdin().read_line(&mut input)?;
+    match input.trim() {
+        "" => {},
+        "-" => meta.note = None,
+        note => meta.note = Some(note.to_string()),
+    }
+    
+    print!("请输入新的标签，多个标签用逗号分隔 (直接回车保留，输入 - 清除): ");
+    let mut input = String::new();
+    std::io::stdin().read_line(&mut input)?;
+    match input.trim() {
+        "" => {},
+        "-" => meta.tags.clear(),
+        tags => meta.tags = parse_tags(tags),
+    }
+    
+    save_meta(config, &snapshot, &meta)?;
+    println!("快照 {} 的备注和标签已更新。", snapshot.file_name());
+    
+    Ok(())
+}
\ No newline at end of file
@@ -0,0 +1,170 @@
+use anyhow::Result;
+use std::fs;
+use std::path::Path;
+
+use crate::config::{Config, SyncItem, ValidationMode};
+use crate::sync::index::{FileEntry, ItemIndex};
+use crate::sync::settings;
+use crate::sync::snapshot;
+
+// 文件比上一个快照中小于该比例时视为可能被截断
+const SHRINK_RATIO: u64 = 2;
+// 上一个快照中小于该大小的文件不做缩小检查，小文件的正常改动就可能减半
+const SHRINK_MIN_SIZE: u64 = 1024;
+
+// 需要校验内容的文件格式
+#[derive(Clone, Copy)]
+enum Kind {
+    Json,
+    Ini,
+    Yaml,
+}
+
+fn kind_of(path: &Path) -> Option<Kind> {
+    let ext = path
+        .extension()
+        .map(|e| e.to_string_lossy().to_lowercase())
+        .unwrap_or_default();
+    
+    match ext.as_str() {
+        "json" => Some(Kind::Json),
+        "ini" | "cfg" => Some(Kind::Ini),
+        "yaml" | "yml" => Some(Kind::Yaml),
+        _ => None,
+    }
+}
+
+// 每个非空行必须是注释、节名或 "键=值"
+fn check_ini(text: &str) -> Option<String> {
+    for (i, line) in settings::strip_bom(text).lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with(';') || line.starts_with('#') {
+            continue;
+        }
+        
+        let is_section = line.starts_with('[') && line.ends_with(']');
+        if !is_section && !line.contains('=') {
+            return Some(format!("第 {} 行不是有效的 INI", i + 1));
+        }
+    }
+    
+    None
+}
+
+// 检查单个文件的内容，返回问题说明
+fn check_content(path: &Path, kind: Kind) -> Option<String> {
+    let bytes = match fs::read(path) {
+        Ok(bytes) => bytes,
+        Err(e) => return Some(format!("无法读取: {}", e)),
+    };
+    
+    if bytes.is_empty() {
+        return Some("文件为空".to_string());
+    }
+    
+    // 客户端写入中途崩溃时，文件常常留下一段空字节
+    if bytes.contains(&0) {
+        return Some("包含空字节，可能写入不完整".to_string());
+    }
+    
+    let text = match String::from_utf8(bytes) {
+        Ok(text) => text,
+        Err(_) => return Some("不是有效的 UTF-8 文本".to_string()),
+    };
+    
+    match kind {
+        Kind::Json => settings::parse_json(&text, "文件").err().map(|e| format!("{:#}", e)),
+        Kind::Ini => check_ini(&text),
+        Kind::Yaml => serde_yaml::from_str::<serde_yaml::Value>(settings::strip_bom(&text))
+            .err()
+            .map(|e| format!("不是有效的 YAML: {}", e)),
+    }
+}
+
+// 校验项目源路径中已知格式的设置文件，并与上一个快照比较大小；返回发现的问题
+pub fn check_item(item: &SyncItem, files: &[FileEntry], previous: Option<&ItemIndex>) -> Vec<String> {
+    check_files(&item.source_path, &item.name, files, previous.map(|p| p.files.as_slice()))
+}
+
+// 校验 root 中已知格式的设置文件，previous 是上一个快照的文件列表；单文件时 name 作为显示名称
+pub fn check_files(root: &Path, name: &str, files: &[FileEntry], previous: Option<&[FileEntry]>) -> Vec<String> {
+    let mut problems = Vec::new();
+    
+    for file in files {
+        let path = if file.path.is_empty() { root.to_path_buf() } else { root.join(&file.path) };
+        let kind = match kind_of(&path) {
+            Some(kind) => kind,
+            None => continue,
+        };
+        
+        let display = if file.path.is_empty() { name } else { file.path.as_str() };
+        
+        if let Some(problem) = check_content(&path, kind) {
+            problems.push(format!("{}: {}", display, problem));
+            continue;
+        }
+        
+        let old_size = previous
+            .and_then(|p| p.iter().find(|f| f.path == file.path))
+            .map(|f| f.size);
+        if let Some(old_size) = old_size {
+            if old_size >= SHRINK_MIN_SIZE && file.size * SHRINK_RATIO < old_size {
+                problems.push(format!("{}: 大小从 {} 字节降到 {} 字节", display, old_size, file.size));
+            }
+        }
+    }
+    
+    problems
+}
+
+// 恢复前检查快照是否被标记为可疑；可疑的快照需要输入 'force' 才能继续
+pub fn confirm_restore(config: &Config, snapshot_path: &Path) -> Result<bool> {
+    let reasons = snapshot::suspect_reasons(config, snapshot_path)?;
+    if reasons.is_empty() {
+        return Ok(true);
+    }
+    
+    println!("快照 {:?} 在备份时被标记为可疑:", snapshot_path);
+    for reason in &reasons {
+        println!("  {}", reason);
+    }
+    
+    print!("请输入'force'强制恢复该快照，直接回车跳过: ");
+    let mut input = String::new();
+    std::io::stdin().read_line(&mut input)?;
+    
+    Ok(input.trim().to_lowercase() == "force")
+}
+
+// 设置备份前校验失败时的处理方式
+pub fn set_validation_mode(config: &mut Config) -> Result<()> {
+    let current = match config.backup_validation {
+        ValidationMode::Warn => "警告并标记为可疑",
+        ValidationMode::Skip => "跳过该项目，不创建快照",
+    };
+    println!("备份前会校验 JSON、INI 和 YAML 设置文件，检查空文件、无法解析的文件以及比上一个快照明显变小的文件。");
+    println!("当前发现问题时的处理方式: {}", current);
+    
+    println!("发现问题时:");
+    println!("  1. 警告并标记为可疑（仍创建快照，恢复时需要强制确认）");
+    println!("  2. 跳过该项目，不创建快照");
+    
+    print!("请选择 (或输入0取消): ");
+    let mut input = String::new();
+    std::io::stdin().read_line(&mut input)?;
+    
+    config.backup_validation = match input.trim() {
+        "1" => ValidationMode::Warn,
+        "2" => ValidationMode::Skip,
+        "0" => return Ok(()),
+        _ => {
+            println!("无效的选择。操作已取消。");
+            return Ok(());
+        }
+    };
+    
+    config.save()?;
+    println!("备份前校验设置已更新。");
+    
+    Ok(())
+}
\ No newline at end of file
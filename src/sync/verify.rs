@@ -0,0 +1,200 @@
+use anyhow::{Context, Result};
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::config::Config;
+use crate::sync::index::{self, FileEntry};
+use crate::sync::restore::{backup_payload, RestoreSession};
+use crate::sync::restore_item_content;
+use crate::sync::snapshot::{self, ScratchDir, Snapshot, GAME_CONFIG_SERIES};
+
+// 一个快照的校验结果
+enum Verdict {
+    // 所有文件都与清单一致，附带文件数
+    Intact(usize),
+    // 旧快照没有记录清单，只确认了能够读取
+    NoManifest,
+    Damaged(Vec<String>),
+}
+
+// 按清单检查 root 下的文件，返回发现的问题
+fn check_against_manifest(root: &Path, manifest: &[FileEntry]) -> Result<Vec<String>> {
+    let actual: HashMap<String, FileEntry> = index::scan_tree(root)?
+        .into_iter()
+        .map(|f| (f.path.clone(), f))
+        .collect();
+    
+    let mut problems = Vec::new();
+    
+    for expected in manifest {
+        let name = if expected.path.is_empty() { "(快照文件)" } else { expected.path.as_str() };
+        
+        match actual.get(&expected.path) {
+            None => problems.push(format!("缺失: {}", name)),
+            Some(found) if found.size < expected.size => problems.push(format!(
+                "截断: {} ({} / {} 字节)",
+                name, found.size, expected.size
+            )),
+            Some(found) if found.size != expected.size || found.hash != expected.hash => {
+                problems.push(format!("损坏: {} (校验值不一致)", name))
+            },
+            Some(_) => {},
+        }
+    }
+    
+    let mut extra: Vec<&String> = actual
+        .keys()
+        .filter(|path| !manifest.iter().any(|f| &f.path == *path))
+        .collect();
+    extra.sort();
+    for path in extra {
+        problems.push(format!("多余: {}", path));
+    }
+    
+    Ok(problems)
+}
+
+// 用与实际恢复相同的逻辑把快照恢复到 dir 中，返回与快照内容根目录对应的路径；
+// 安全快照和日志也放在 dir 中，不影响源路径和用户可以撤销的上一次恢复
+fn restore_to_scratch(config: &Config, series: &str, snap: &Snapshot, dir: &Path) -> Result<PathBuf> {
+    let restored = dir.join("restored");
+    let mut session = RestoreSession::begin_at(config, dir.join("safety"));
+    
+    if let Some(item) = config.sync_items.iter().find(|item| item.name == series) {
+        let mut target_item = item.clone();
+        target_item.source_path = restored.join(item.source_path.file_name().unwrap_or_else(|| std::ffi::OsStr::new("backup")));
+        restore_item_content(&mut session, &target_item, &snap.path)?;
+        return Ok(restored);
+    }
+    
+    let content = snapshot::open_snapshot(config, &snap.path)?;
+    
+    if series == GAME_CONFIG_SERIES {
+        let target = restored.join(config.game_config_path.file_name().unwrap_or_else(|| std::ffi::OsStr::new("config")));
+        let payload = backup_payload(content.path(), &config.game_config_path);
+        session.restore(&payload, &target)?;
+        
+        // 快照根目录就是配置内容（单文件或直接存放目录内容）时，清单路径相对于恢复出来的配置本身；
+        // 旧快照把目录存放在同名子目录中，清单路径包含该子目录
+        return Ok(if payload == content.path() { target } else { restored });
+    }
+    
+    // 脚本快照中的每个条目（"{index}_{name}"）分别恢复
+    let entries = fs::read_dir(content.path()).with_context(|| format!("读取快照失败: {:?}", snap.path))?;
+    for entry in entries {
+        let entry = entry?;
+        let script_path = entry
+            .file_name()
+            .to_string_lossy()
+            .split_once('_')
+            .and_then(|(index, _)| index.parse::<usize>().ok())
+            .and_then(|index| config.script_paths.get(index));
+        let payload = match script_path {
+            Some(script_path) => backup_payload(&entry.path(), script_path),
+            None => entry.path(),
+        };
+        session.restore(&payload, &restored.join(entry.file_name()))?;
+    }
+    
+    Ok(restored)
+}
+
+// 校验一个快照；test_restore 时先用实际的恢复逻辑把快照恢复到临时目录，再检查恢复出来的文件
+fn verify_snapshot(config: &Config, series: &str, snap: &Snapshot, test_restore: bool) -> Result<Verdict> {
+    // 快照信息无法读取（如无法解密）时只报告该快照，继续校验其他快照
+    let manifest = match snapshot::load_meta(config, snap) {
+        Ok(meta) => meta.manifest,
+        Err(e) => return Ok(Verdict::Damaged(vec![format!("无法读取快照信息: {:#}", e)])),
+    };
+    
+    // 测试恢复时由恢复逻辑自己打开快照，这里只在直接检查时打开，避免重复解密、解压
+    let scratch = ScratchDir::new("verify");
+    let content;
+    let root = if test_restore {
+        match restore_to_scratch(config, series, snap, scratch.path()) {
+            Ok(root) => root,
+            Err(e) => return Ok(Verdict::Damaged(vec![format!("测试恢复失败: {:#}", e)])),
+        }
+    } else {
+        // 读取失败（无法解密、解压）本身就说明快照已损坏
+        content = match snapshot::open_snapshot(config, &snap.path) {
+            Ok(content) => content,
+            Err(e) => return Ok(Verdict::Damaged(vec![format!("无法读取: {:#}", e)])),
+        };
+        content.path().to_path_buf()
+    };
+    
+    let manifest = match manifest {
+        Some(manifest) => manifest,
+        None => {
+            // 没有清单时至少要能读出所有文件
+            return Ok(match index::scan_tree(&root) {
+                Ok(_) => Verdict::NoManifest,
+                Err(e) => Verdict::Damaged(vec![format!("无法读取: {:#}", e)]),
+            });
+        }
+    };
+    
+    let problems = check_against_manifest(&root, &manifest)?;
+    if problems.is_empty() {
+        Ok(Verdict::Intact(manifest.len()))
+    } else {
+        Ok(Verdict::Damaged(problems))
+    }
+}
+
+// 校验备份目录中的所有快照，按系列报告缺失、截断和损坏的文件
+pub fn verify_backups(config: &Config, test_restore: bool) -> Result<()> {
+    if test_restore {
+        println!("正在把每个快照恢复到临时目录并与清单比较...");
+    } else {
+        println!("正在校验所有快照...");
+    }
+    
+    let mut intact = 0;
+    let mut damaged = 0;
+    let mut unchecked = 0;
+    
+    for series in snapshot::all_series(config) {
+        let snapshots = snapshot::list_snapshots(config, &series)?;
+        if snapshots.is_empty() {
+            continue;
+        }
+        
+        println!("\n{}:", series);
+        
+        for snap in &snapshots {
+            let verdict = verify_snapshot(config, &series, snap, test_restore)
+                .unwrap_or_else(|e| Verdict::Damaged(vec![format!("校验失败: {:#}", e)]));
+            match verdict {
+                Verdict::Intact(count) => {
+                    intact += 1;
+                    println!("  完好 {} ({} 个文件)", snap.file_name(), count);
+                },
+                Verdict::NoManifest => {
+                    unchecked += 1;
+                    println!("  无清单 {} (可以读取，但没有记录校验值)", snap.file_name());
+                },
+                Verdict::Damaged(problems) => {
+                    damaged += 1;
+                    println!("  损坏 {}:", snap.file_name());
+                    for problem in problems {
+                        println!("    {}", problem);
+                    }
+                },
+            }
+        }
+    }
+    
+    println!(
+        "\n校验完成：完好 {} 个，损坏 {} 个，无清单 {} 个。",
+        intact, damaged, unchecked
+    );
+    
+    if damaged > 0 {
+        println!("损坏的快照无法可靠恢复，建议尽快重新备份相应的项目。");
+    }
+    
+    Ok(())
+}
@@ -0,0 +1,89 @@
+use anyhow::Result;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::config::Config;
+use crate::sync::{settings, snapshot};
+
+// 安装目录中记录版本号的文件，按顺序查找：游戏本体的版本优先于客户端
+const VERSION_FILES: [&str; 2] = ["Game/content-metadata.json", "content-metadata.json"];
+
+// 游戏安装目录；没有单独设置时使用游戏配置目录的上一级，如 C:\Riot Games\League of Legends
+fn install_dir(config: &Config) -> Option<PathBuf> {
+    config
+        .game_install_dir
+        .clone()
+        .or_else(|| config.game_config_path.parent().map(Path::to_path_buf))
+}
+
+// 读取 content-metadata.json 中的版本号，去掉 '+' 之后的分支信息，如 "14.20.624.1234"
+fn read_version_file(path: &Path) -> Option<String> {
+    let text = fs::read_to_string(path).ok()?;
+    let value: serde_json::Value = serde_json::from_str(settings::strip_bom(&text)).ok()?;
+    let version = value.get("version")?.as_str()?;
+    let version = version.split('+').next().unwrap_or(version).trim();
+    
+    if version.is_empty() {
+        None
+    } else {
+        Some(version.to_string())
+    }
+}
+
+// 检测已安装的游戏版本，找不到版本文件时返回 None
+pub fn detect_game_version(config: &Config) -> Option<String> {
+    let dir = install_dir(config)?;
+    VERSION_FILES
+        .iter()
+        .find_map(|file| read_version_file(&dir.join(file)))
+}
+
+// 大版本号，即赛季和补丁号，如 "14.20.624.1234" -> "14.20"
+pub fn major_patch(version: &str) -> String {
+    version.split('.').take(2).collect::<Vec<_>>().join(".")
+}
+
+// 在新建的快照中记录当前的游戏版本
+pub fn record_version(config: &Config, snapshot_path: &Path) -> Result<()> {
+    match detect_game_version(config) {
+        Some(version) => snapshot::save_game_version(config, snapshot_path, version),
+        None => Ok(()),
+    }
+}
+
+// 快照列表中显示的版本标记
+pub fn version_label(config: &Config, snapshot_path: &Path) -> String {
+    match snapshot::game_version(config, snapshot_path) {
+        Ok(Some(version)) => format!(" [版本 {}]", version),
+        _ => String::new(),
+    }
+}
+
+// 恢复前比较快照和当前安装的大版本；不同时设置格式可能已经变化，需要确认
+pub fn confirm_version(config: &Config, snapshot_path: &Path) -> Result<bool> {
+    let snapshot_version = match snapshot::game_version(config, snapshot_path)? {
+        Some(version) => version,
+        None => return Ok(true),
+    };
+    let current = match detect_game_version(config) {
+        Some(version) => version,
+        None => return Ok(true),
+    };
+    
+    if major_patch(&snapshot_version) == major_patch(&current) {
+        return Ok(true);
+    }
+    
+    println!(
+        "警告: 快照 {:?} 创建于游戏版本 {}，当前安装的版本是 {}。",
+        snapshot_path.file_name().unwrap_or_default(),
+        snapshot_version,
+        current
+    );
+    println!("不同补丁之间的设置格式可能已经变化，恢复后游戏可能会重置或忽略部分设置。");
+    print!("请输入'yes'继续恢复该快照: ");
+    let mut input = String::new();
+    std::io::stdin().read_line(&mut input)?;
+    
+    Ok(input.trim().to_lowercase() == "yes")
+}
\ No newline at end of file
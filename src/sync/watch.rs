@@ -0,0 +1,244 @@
+use anyhow::{Context, Result};
+use notify::{Event, EventKind, RecommendedWatcher, RecursiveMode, Watcher};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use crate::config::{Config, SyncItem};
+use crate::sync::filter::PathFilter;
+use crate::sync::drift;
+use crate::sync::{backup_item, BackupOutcome};
+
+// 检查源路径是否出现/消失以及是否到了备份时间的间隔
+const POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+#[derive(PartialEq)]
+enum WatchState {
+    Watching,
+    // 源路径不存在，等待它重新出现
+    Missing,
+    // 源路径存在但无法监视，下次检查时重试
+    Failed,
+}
+
+struct WatchedItem<'a> {
+    item: &'a SyncItem,
+    filter: PathFilter,
+    state: WatchState,
+    // 实际注册到监视器的路径
+    watch_path: Option<PathBuf>,
+    // 最后一次检测到变化的时间，防抖结束后才备份
+    pending: Option<Instant>,
+}
+
+pub fn log(message: &str) {
+    println!("[{}] {}", chrono::Local::now().format("%Y-%m-%d %H:%M:%S"), message);
+}
+
+// 在后台等待用户按回车，返回的标志在按下后变为 true；用于可随时停止的长时间任务
+pub fn stop_on_enter() -> Arc<AtomicBool> {
+    let stop = Arc::new(AtomicBool::new(false));
+    let flag = stop.clone();
+    
+    thread::spawn(move || {
+        let mut input = String::new();
+        let _ = std::io::stdin().read_line(&mut input);
+        flag.store(true, Ordering::SeqCst);
+    });
+    
+    stop
+}
+
+// 目录监视整个目录；文件监视其所在目录，这样客户端以"写临时文件再改名"的方式保存时也能收到事件
+fn watch_target(source: &Path) -> Option<(PathBuf, RecursiveMode)> {
+    if source.is_dir() {
+        Some((source.to_path_buf(), RecursiveMode::Recursive))
+    } else if source.exists() {
+        source
+            .parent()
+            .map(|parent| (parent.to_path_buf(), RecursiveMode::NonRecursive))
+    } else {
+        None
+    }
+}
+
+// 为新出现的源路径注册监视，为消失的源路径取消监视
+fn refresh_watches(watcher: &mut RecommendedWatcher, items: &mut [WatchedItem]) {
+    for watched in items.iter_mut() {
+        let source = &watched.item.source_path;
+        
+        match watch_target(source) {
+            Some((path, mode)) if watched.state != WatchState::Watching => {
+                match watcher.watch(&path, mode) {
+                    Ok(()) => {
+                        if watched.state == WatchState::Missing && watched.watch_path.is_some() {
+                            // 源路径重新出现，内容很可能已经变化
+                            log(&format!("{} 的源路径已重新出现: {:?}", watched.item.name, source));
+                            watched.pending = Some(Instant::now());
+                        } else {
+                            log(&format!("开始监视 {}: {:?}", watched.item.name, source));
+                        }
+                        watched.state = WatchState::Watching;
+                        watched.watch_path = Some(path);
+                    },
+                    Err(e) => {
+                        if watched.state != WatchState::Failed {
+                            log(&format!("无法监视 {}，稍后重试: {}", watched.item.name, e));
+                        }
+                        watched.state = WatchState::Failed;
+                    },
+                }
+            },
+            None if watched.state != WatchState::Missing => {
+                if watched.state == WatchState::Watching {
+                    if let Some(path) = &watched.watch_path {
+                        let _ = watcher.unwatch(path);
+                    }
+                    log(&format!("{} 的源路径已消失，等待其重新出现: {:?}", watched.item.name, source));
+                } else {
+                    log(&format!("{} 的源路径不存在，等待其出现: {:?}", watched.item.name, source));
+                }
+                watched.state = WatchState::Missing;
+                watched.pending = None;
+            },
+            _ => {},
+        }
+    }
+}
+
+// 把文件系统事件对应到同步项目；被过滤规则排除的文件和备份目录中的变化会被忽略
+fn mark_changed(config: &Config, items: &mut [WatchedItem], event: &Event) {
+    if event.kind.is_access() {
+        return;
+    }
+    
+    for path in &event.paths {
+        if path.starts_with(&config.backup_dir) {
+            continue;
+        }
+        
+        for watched in items.iter_mut() {
+            if watched.state != WatchState::Watching {
+                continue;
+            }
+            
+            let rel = match path.strip_prefix(&watched.item.source_path) {
+                Ok(rel) => rel,
+                Err(_) => continue,
+            };
+            
+            // 源路径本身被删除或替换时，原来的监视已经失效，下次检查时重新注册
+            if rel.as_os_str().is_empty() && matches!(event.kind, EventKind::Remove(_)) {
+                log(&format!("{} 的源路径已被删除或替换: {:?}", watched.item.name, path));
+                watched.state = WatchState::Missing;
+                watched.pending = None;
+                continue;
+            }
+            
+            if !rel.as_os_str().is_empty() && !watched.filter.is_included(rel, path.is_dir()) {
+                continue;
+            }
+            
+            if watched.pending.is_none() {
+                log(&format!("检测到 {} 的变化: {:?}", watched.item.name, path));
+            }
+            watched.pending = Some(Instant::now());
+        }
+    }
+}
+
+// 备份防抖时间已过的项目；force 时立即备份所有有未处理变化的项目
+fn run_due_backups(config: &Config, items: &mut [WatchedItem], debounce: Duration, force: bool) {
+    for watched in items.iter_mut() {
+        let due = match watched.pending {
+            Some(last) => force || last.elapsed() >= debounce,
+            None => false,
+        };
+        if !due {
+            continue;
+        }
+        
+        watched.pending = None;
+        
+        if !watched.item.source_path.exists() {
+            continue;
+        }
+        
+        // 设置了黄金快照的项目先检查漂移；修复会再次触发变化，届时再备份
+        if watched.item.golden.is_some() && drift::check_and_log(config, watched.item) {
+            continue;
+        }
+        
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_secs();
+        
+        // 单个项目备份失败不影响继续监视
+        match backup_item(config, watched.item, timestamp) {
+            Ok(BackupOutcome::Created(_)) => log(&format!("已自动备份 {}", watched.item.name)),
+            Ok(BackupOutcome::Unchanged(_)) => log(&format!("{} 的内容没有变化，未创建新快照", watched.item.name)),
+            Ok(BackupOutcome::Rejected) => log(&format!("{} 的设置文件未通过校验，未创建新快照", watched.item.name)),
+            Err(e) => log(&format!("自动备份 {} 失败: {:#}", watched.item.name, e)),
+        }
+    }
+}
+
+// 监视所有启用的同步项目，源路径变化并稳定下来后自动备份该项目；按回车键停止
+pub fn watch_sync_items(config: &Config) -> Result<()> {
+    let mut items = Vec::new();
+    for item in config.sync_items.iter().filter(|item| item.is_enabled) {
+        items.push(WatchedItem {
+            item,
+            filter: PathFilter::for_item(item)?,
+            state: WatchState::Failed,
+            watch_path: None,
+            pending: None,
+        });
+    }
+    
+    if items.is_empty() {
+        println!("没有启用的同步项目。");
+        return Ok(());
+    }
+    
+    let (tx, rx) = mpsc::channel();
+    let mut watcher = notify::recommended_watcher(move |res| {
+        let _ = tx.send(res);
+    })
+    .with_context(|| "创建文件监视器失败")?;
+    
+    let stop = stop_on_enter();
+    
+    let debounce = Duration::from_secs(config.watch_debounce_secs.max(1));
+    println!(
+        "监视模式已启动：源路径变化 {} 秒内没有新的写入后自动备份。按回车键停止。",
+        debounce.as_secs()
+    );
+    
+    while !stop.load(Ordering::SeqCst) {
+        refresh_watches(&mut watcher, &mut items);
+        
+        match rx.recv_timeout(POLL_INTERVAL) {
+            Ok(Ok(event)) => mark_changed(config, &mut items, &event),
+            Ok(Err(e)) => log(&format!("监视出错: {}", e)),
+            Err(RecvTimeoutError::Timeout) => {},
+            Err(RecvTimeoutError::Disconnected) => break,
+        }
+        
+        run_due_backups(config, &mut items, debounce, false);
+    }
+    
+    // 退出前补上还没来得及备份的变化
+    while let Ok(Ok(event)) = rx.try_recv() {
+        mark_changed(config, &mut items, &event);
+    }
+    run_due_backups(config, &mut items, debounce, true);
+    
+    log("监视模式已停止");
+    
+    Ok(())
+}
\ No newline at end of file